@@ -0,0 +1,379 @@
+use csv::Writer;
+use error::Result;
+use serde_json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, Write};
+use util;
+
+/// Options for a trace analysis
+pub struct AnalyzeOptions<'a> {
+  pub input: Option<&'a str>,
+  pub output: Option<&'a str>,
+  pub format: &'a str,
+  pub top: usize,
+}
+
+/// Number of requests for a single page
+#[derive(Debug, Serialize)]
+pub struct PageCount {
+  pub page: u64,
+  pub count: u64,
+}
+
+/// A histogram bucket counting values in `start..=end`
+#[derive(Debug, Serialize)]
+pub struct Bucket {
+  pub start: u64,
+  pub end: u64,
+  pub count: u64,
+}
+
+impl Bucket {
+  /// Formats the bucket range, single values are not shown as a range
+  fn label(&self) -> String {
+    if self.start == self.end {
+      self.start.to_string()
+    } else {
+      format!("{}-{}", self.start, self.end)
+    }
+  }
+}
+
+/// Histogram with log2 sized buckets: 0, 1, 2-3, 4-7, ...
+#[derive(Default)]
+pub struct LogHistogram {
+  counts: Vec<u64>,
+}
+
+impl LogHistogram {
+  pub fn add(&mut self, value: u64) {
+    let index = (64 - value.leading_zeros()) as usize;
+    if index >= self.counts.len() {
+      self.counts.resize(index + 1, 0);
+    }
+
+    self.counts[index] += 1;
+  }
+
+  pub fn buckets(&self) -> Vec<Bucket> {
+    self.counts
+      .iter()
+      .enumerate()
+      .map(|(i, &count)| {
+        let (start, end) = if i == 0 {
+          (0, 0)
+        } else {
+          let start = 1u64 << (i - 1);
+          // written this way to not overflow on the last bucket
+          (start, start + (start - 1))
+        };
+
+        Bucket { start, end, count }
+      })
+      .collect()
+  }
+}
+
+/// Histogram with a bucket for every value that occurs
+#[derive(Default)]
+pub struct ExactHistogram {
+  counts: BTreeMap<u64, u64>,
+}
+
+impl ExactHistogram {
+  pub fn add(&mut self, value: u64) {
+    *self.counts.entry(value).or_insert(0) += 1;
+  }
+
+  pub fn buckets(&self) -> Vec<Bucket> {
+    self.counts
+      .iter()
+      .map(|(&value, &count)| Bucket { start: value, end: value, count })
+      .collect()
+  }
+}
+
+/// Binary indexed tree, used to count how many pages had their most
+/// recent request within a range of positions in the trace
+pub struct Fenwick {
+  tree: Vec<i64>,
+}
+
+impl Fenwick {
+  pub fn new(size: usize) -> Self {
+    Fenwick {
+      tree: vec![0; size + 1],
+    }
+  }
+
+  /// Number of values in the tree
+  pub fn len(&self) -> usize {
+    self.tree.len() - 1
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Appends a value after the last index, for traces of unknown length
+  pub fn push(&mut self, value: i64) {
+    // the new node covers the values from index - lowbit up to itself
+    let index = self.tree.len();
+    let low = index & index.wrapping_neg();
+    let node = value + self.sum(index - 1) - self.sum(index - low);
+    self.tree.push(node);
+  }
+
+  /// Adds delta to the value at index
+  pub fn add(&mut self, index: usize, delta: i64) {
+    let mut i = index + 1;
+    while i < self.tree.len() {
+      self.tree[i] += delta;
+      i += i & i.wrapping_neg();
+    }
+  }
+
+  /// Sum of values in 0..index
+  pub fn sum(&self, index: usize) -> i64 {
+    let mut i = index;
+    let mut total = 0;
+    while i > 0 {
+      total += self.tree[i];
+      i -= i & i.wrapping_neg();
+    }
+
+    total
+  }
+}
+
+/// Calculates the reuse (LRU stack) distance of every request, which is the
+/// number of distinct pages requested since the previous request of the
+/// same page. First requests of a page have no reuse distance.
+pub fn reuse_distances(requests: &[u64]) -> Vec<Option<u64>> {
+  let mut last_seen: HashMap<u64, usize> = HashMap::new();
+  // 1 at positions that are currently the latest request of some page
+  let mut latest = Fenwick::new(requests.len());
+  let mut distances = Vec::with_capacity(requests.len());
+
+  for (i, &page) in requests.iter().enumerate() {
+    let distance = last_seen.get(&page).map(|&prev| {
+      let distance = latest.sum(i) - latest.sum(prev + 1);
+      latest.add(prev, -1);
+      distance as u64
+    });
+
+    latest.add(i, 1);
+    last_seen.insert(page, i);
+    distances.push(distance);
+  }
+
+  distances
+}
+
+/// Calculates the inter-reference gap of every request, which is the
+/// number of requests since the previous request of the same page. First
+/// requests of a page have no gap.
+pub fn reference_gaps(requests: &[u64]) -> Vec<Option<u64>> {
+  let mut last_seen: HashMap<u64, usize> = HashMap::new();
+  requests
+    .iter()
+    .enumerate()
+    .map(|(i, &page)| last_seen.insert(page, i).map(|prev| (i - prev) as u64))
+    .collect()
+}
+
+/// Statistics of a page request trace
+#[derive(Debug, Serialize)]
+pub struct TraceStats {
+  /// Number of valid page requests
+  pub length: u64,
+  /// Number of unique pages requested
+  pub distinct_pages: u64,
+  /// Lowest page number requested
+  pub min_page: Option<u64>,
+  /// Highest page number requested
+  pub max_page: Option<u64>,
+  /// Fraction of requests for the page right after the previous request
+  pub sequentiality: f64,
+  /// Number of first requests of a page, these have no reuse distance
+  pub cold_requests: u64,
+  /// Most requested pages, most requested first
+  pub top_pages: Vec<PageCount>,
+  /// Number of pages bucketed by how many times they were requested
+  pub frequency_histogram: Vec<Bucket>,
+  /// Number of requests bucketed by reuse distance
+  pub reuse_distances: Vec<Bucket>,
+  /// Number of requests for each page, sorted by page number
+  pub frequencies: Vec<PageCount>,
+}
+
+impl TraceStats {
+  /// Calculates statistics for a trace, keeping the `top` most requested pages
+  pub fn new(requests: &[u64], top: usize) -> Self {
+    let mut counts: HashMap<u64, u64> = HashMap::new();
+    for &page in requests {
+      *counts.entry(page).or_insert(0) += 1;
+    }
+
+    let mut frequencies: Vec<PageCount> = counts
+      .into_iter()
+      .map(|(page, count)| PageCount { page, count })
+      .collect();
+    frequencies.sort_by_key(|x| x.page);
+
+    // most requested first, ties by page number
+    let mut top_pages: Vec<PageCount> = frequencies
+      .iter()
+      .map(|x| PageCount { page: x.page, count: x.count })
+      .collect();
+    top_pages.sort_by(|a, b| b.count.cmp(&a.count).then(a.page.cmp(&b.page)));
+    top_pages.truncate(top);
+
+    let mut frequency_histogram = LogHistogram::default();
+    for x in &frequencies {
+      frequency_histogram.add(x.count);
+    }
+
+    let mut reuse_histogram = LogHistogram::default();
+    let mut cold_requests = 0;
+    for distance in reuse_distances(requests) {
+      match distance {
+        Some(distance) => reuse_histogram.add(distance),
+        None => cold_requests += 1,
+      }
+    }
+
+    let sequential = requests
+      .windows(2)
+      .filter(|pair| pair[0].checked_add(1) == Some(pair[1]))
+      .count();
+    let sequentiality = if requests.len() > 1 {
+      sequential as f64 / (requests.len() - 1) as f64
+    } else {
+      0.0
+    };
+
+    TraceStats {
+      length: requests.len() as u64,
+      distinct_pages: frequencies.len() as u64,
+      min_page: frequencies.first().map(|x| x.page),
+      max_page: frequencies.last().map(|x| x.page),
+      sequentiality,
+      cold_requests,
+      top_pages,
+      frequency_histogram: frequency_histogram.buckets(),
+      reuse_distances: reuse_histogram.buckets(),
+      frequencies,
+    }
+  }
+
+  /// Writes a human readable summary, per page frequencies are omitted
+  pub fn write_text<W: Write>(&self, out: &mut W) -> Result<()> {
+    let range = match (self.min_page, self.max_page) {
+      (Some(min), Some(max)) => format!("{} - {}", min, max),
+      _ => "-".to_string(),
+    };
+
+    writeln!(out, "{:<18}{}", "Requests", self.length)?;
+    writeln!(out, "{:<18}{}", "Distinct pages", self.distinct_pages)?;
+    writeln!(out, "{:<18}{}", "Page range", range)?;
+    writeln!(out, "{:<18}{:.5}", "Sequentiality", self.sequentiality)?;
+    writeln!(out, "{:<18}{}", "Cold requests", self.cold_requests)?;
+
+    writeln!(out, "\nTop {} pages", self.top_pages.len())?;
+    writeln!(out, "  {:<12}{:<12}share", "page", "requests")?;
+    for x in &self.top_pages {
+      writeln!(out, "  {:<12}{:<12}{:.5}",
+        x.page, x.count, x.count as f64 / self.length as f64)?;
+    }
+
+    writeln!(out, "\nPages by number of requests")?;
+    writeln!(out, "  {:<12}pages", "requests")?;
+    for bucket in &self.frequency_histogram {
+      writeln!(out, "  {:<12}{}", bucket.label(), bucket.count)?;
+    }
+
+    writeln!(out, "\nRequests by reuse distance")?;
+    writeln!(out, "  {:<12}requests", "distance")?;
+    for bucket in &self.reuse_distances {
+      writeln!(out, "  {:<12}{}", bucket.label(), bucket.count)?;
+    }
+
+    Ok(())
+  }
+
+  /// Writes all statistics as a single JSON object
+  pub fn write_json<W: Write>(&self, out: &mut W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, self)?;
+    writeln!(out)?;
+
+    Ok(())
+  }
+
+  /// Writes all statistics as long format csv with the columns
+  /// `stat,key,value`, ex: `frequency,5072,150000`
+  pub fn write_csv<W: Write>(&self, out: W) -> Result<()> {
+    let mut wtr = Writer::from_writer(out);
+    let optional = |x: Option<u64>| x.map(|x| x.to_string()).unwrap_or_default();
+
+    wtr.write_record(["stat", "key", "value"])?;
+    wtr.write_record(["summary", "length", &self.length.to_string()])?;
+    wtr.write_record(["summary", "distinct_pages", &self.distinct_pages.to_string()])?;
+    wtr.write_record(["summary", "min_page", &optional(self.min_page)])?;
+    wtr.write_record(["summary", "max_page", &optional(self.max_page)])?;
+    wtr.write_record(["summary", "sequentiality", &self.sequentiality.to_string()])?;
+    wtr.write_record(["summary", "cold_requests", &self.cold_requests.to_string()])?;
+
+    for x in &self.top_pages {
+      wtr.write_record(["top_page", &x.page.to_string(), &x.count.to_string()])?;
+    }
+    for bucket in &self.frequency_histogram {
+      wtr.write_record(["frequency_histogram", &bucket.label(), &bucket.count.to_string()])?;
+    }
+    for bucket in &self.reuse_distances {
+      wtr.write_record(["reuse_distance", &bucket.label(), &bucket.count.to_string()])?;
+    }
+    for x in &self.frequencies {
+      wtr.write_record(["frequency", &x.page.to_string(), &x.count.to_string()])?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+  }
+}
+
+/// Analyzes a trace and writes the statistics to the output file or stdout
+pub fn analyze(options: AnalyzeOptions) -> Result<()> {
+  let requests = util::read_requests(options.input)?;
+  let stats = TraceStats::new(&requests, options.top);
+
+  let mut out: Box<dyn Write> = match options.output {
+    Some(output) => Box::new(File::create(output)?),
+    None => Box::new(io::stdout()),
+  };
+
+  match options.format {
+    "json" => stats.write_json(&mut out)?,
+    "csv" => stats.write_csv(&mut out)?,
+    _ => stats.write_text(&mut out)?,
+  }
+
+  if let Some(output) = options.output {
+    info!("Saved trace analysis to {}", output);
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sequentiality_counts_next_pages() {
+    let stats = TraceStats::new(&[1, 2, 3, 5, u64::MAX, 0, u64::MAX], 10);
+    assert_eq!(stats.sequentiality, 2.0 / 6.0);
+    assert_eq!((stats.min_page, stats.max_page), (Some(0), Some(u64::MAX)));
+  }
+}
@@ -0,0 +1,145 @@
+use csv::Writer;
+use error::Result;
+use model::cache::{CachePolicy, ObjectCache};
+use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use util;
+
+/// Options for simulating object caches
+pub struct CacheOptions<'a> {
+  pub input: Option<&'a str>,
+  pub output: Option<&'a str>,
+  pub format: &'a str,
+  /// Capacities to simulate in bytes
+  pub capacities: Vec<u64>,
+  pub algorithms: Vec<&'a str>,
+}
+
+/// Results of a single cache simulation
+#[derive(Debug, Serialize)]
+pub struct CacheResult {
+  pub algorithm: String,
+  /// Capacity in bytes
+  pub capacity: u64,
+  pub requests: u64,
+  pub hits: u64,
+  /// Fraction of requested objects that were cached
+  pub hit_rate: f64,
+  /// Total size of requested objects in bytes
+  pub bytes: u64,
+  pub hit_bytes: u64,
+  /// Fraction of requested bytes that were cached
+  pub byte_hit_rate: f64,
+  pub evictions: u64,
+  /// Requests for objects larger than the cache
+  pub bypassed: u64,
+}
+
+fn rate(count: u64, total: u64) -> f64 {
+  if total == 0 {
+    return 0.0;
+  }
+
+  count as f64 / total as f64
+}
+
+impl CacheResult {
+  /// Runs every request through a new cache
+  fn new(algorithm: &str, capacity: u64, requests: &[(u64, u64)]) -> Self {
+    let mut cache = ObjectCache::new(CachePolicy::new(algorithm), capacity);
+    for &(key, size) in requests {
+      cache.request(&key, size);
+    }
+
+    CacheResult {
+      algorithm: algorithm.to_string(),
+      capacity: cache.capacity(),
+      requests: cache.requests(),
+      hits: cache.hits(),
+      hit_rate: rate(cache.hits(), cache.requests()),
+      bytes: cache.bytes(),
+      hit_bytes: cache.hit_bytes(),
+      byte_hit_rate: rate(cache.hit_bytes(), cache.bytes()),
+      evictions: cache.evictions(),
+      bypassed: cache.bypassed(),
+    }
+  }
+}
+
+/// Writes a table of every cache simulation
+fn write_text<W: Write>(out: &mut W, results: &[CacheResult]) -> Result<()> {
+  writeln!(out, "{:<12}{:<16}{:<12}{:<12}{:<16}{:<12}bypassed",
+    "algorithm", "capacity", "requests", "hit rate", "byte hit rate", "evictions")?;
+  for x in results {
+    writeln!(out, "{:<12}{:<16}{:<12}{:<12.5}{:<16.5}{:<12}{}",
+      x.algorithm, x.capacity, x.requests, x.hit_rate, x.byte_hit_rate, x.evictions, x.bypassed)?;
+  }
+
+  Ok(())
+}
+
+/// Writes every cache simulation as a single JSON array
+fn write_json<W: Write>(out: &mut W, results: &[CacheResult]) -> Result<()> {
+  serde_json::to_writer_pretty(&mut *out, results)?;
+  writeln!(out)?;
+
+  Ok(())
+}
+
+/// Writes a csv row for every cache simulation
+fn write_csv<W: Write>(out: W, results: &[CacheResult]) -> Result<()> {
+  let mut wtr = Writer::from_writer(out);
+  for x in results {
+    wtr.serialize(x)?;
+  }
+  wtr.flush()?;
+
+  Ok(())
+}
+
+/// Simulates object caches for each algorithm and capacity, writes the
+/// results to the output file or stdout
+pub fn cache(options: CacheOptions) -> Result<()> {
+  let requests = util::read_object_requests(options.input)?;
+
+  // keys are only compared, numbering them once avoids hashing strings
+  // in every simulation
+  let mut keys = HashMap::new();
+  let requests: Vec<(u64, u64)> = requests
+    .into_iter()
+    .map(|(key, size)| {
+      let next = keys.len() as u64;
+      (*keys.entry(key).or_insert(next), size)
+    })
+    .collect();
+  info!("Read {} object requests for {} objects", requests.len(), keys.len());
+
+  let mut results = Vec::new();
+  for algorithm in &options.algorithms {
+    for &capacity in &options.capacities {
+      let result = CacheResult::new(algorithm, capacity, &requests);
+      info!("{} with capacity {}: hit rate {:.5}, byte hit rate {:.5}",
+        algorithm.to_uppercase(), capacity, result.hit_rate, result.byte_hit_rate);
+      results.push(result);
+    }
+  }
+
+  let mut out: Box<dyn Write> = match options.output {
+    Some(output) => Box::new(File::create(output)?),
+    None => Box::new(io::stdout()),
+  };
+
+  match options.format {
+    "json" => write_json(&mut out, &results)?,
+    "csv" => write_csv(&mut out, &results)?,
+    _ => write_text(&mut out, &results)?,
+  }
+
+  if let Some(output) = options.output {
+    info!("Saved cache results to {}", output);
+  }
+
+  Ok(())
+}
@@ -0,0 +1,179 @@
+use csv::{Reader, Writer, WriterBuilder};
+use error::{Error, Result};
+use model::algorithms::AlgorithmParams;
+use model::prefetch::Prefetcher;
+use model::simulation::{CostModel, SimulationResult};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+/// Settings that change the results of every simulation, a checkpoint is
+/// only resumed with the settings it was saved with
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunSettings {
+  pub cost: CostModel,
+  /// Name of the prefetcher, empty without one
+  pub prefetcher: String,
+  /// Number of pages loaded by the prefetcher, or the cluster size
+  pub prefetch_degree: u64,
+  pub nru_interval: u64,
+}
+
+impl RunSettings {
+  pub fn new(cost: CostModel, prefetcher: &Option<Prefetcher>, params: &AlgorithmParams)
+    -> Self {
+    let (prefetcher, prefetch_degree) = match *prefetcher {
+      Some(Prefetcher::Sequential { degree }) => ("sequential", degree),
+      Some(Prefetcher::Stride { degree, .. }) => ("stride", degree),
+      Some(Prefetcher::Cluster { size }) => ("cluster", size),
+      None => ("", 0),
+    };
+
+    RunSettings {
+      cost,
+      prefetcher: prefetcher.to_string(),
+      prefetch_degree,
+      nru_interval: params.nru_interval,
+    }
+  }
+}
+
+impl Display for RunSettings {
+  fn fmt(&self, f: &mut Formatter) -> FmtResult {
+    write!(f, "memory {} ns, fault {} ns, write back {} ns, ", self.cost.memory_ns,
+      self.cost.fault_ns, self.cost.write_back_ns)?;
+    if self.prefetcher.is_empty() {
+      write!(f, "no prefetcher")?;
+    } else {
+      write!(f, "{} prefetcher of degree {}", self.prefetcher, self.prefetch_degree)?;
+    }
+    write!(f, ", NRU interval {}", self.nru_interval)
+  }
+}
+
+/// A completed simulation saved to the checkpoint file
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointRecord {
+  algorithm: String,
+  /// Seed of randomized algorithms
+  seed: u64,
+  table_size: usize,
+  hit_rate: f64,
+  elapsed_secs: f64,
+  effective_access_ns: f64,
+  total_ns: f64,
+  demand_faults: u64,
+  prefetch_used: u64,
+  prefetch_wasted: u64,
+  memory_ns: f64,
+  fault_ns: f64,
+  write_back_ns: f64,
+  prefetcher: String,
+  prefetch_degree: u64,
+  nru_interval: u64,
+}
+
+/// Appends results to a csv file as soon as each simulation completes, so
+/// an interrupted range of simulations can be resumed without losing
+/// finished sizes
+pub struct Checkpoint {
+  wtr: Writer<File>,
+  settings: RunSettings,
+}
+
+impl Checkpoint {
+  /// Creates the checkpoint file, or appends to it if `resume` is set and
+  /// it already exists
+  pub fn create(path: &str, resume: bool, settings: RunSettings) -> Result<Self> {
+    // header is only written once, appended rows share it
+    let has_header = resume && Path::new(path).metadata()
+      .map(|x| x.len() > 0)
+      .unwrap_or(false);
+
+    let file = OpenOptions::new()
+      .create(true)
+      .write(true)
+      .append(resume)
+      .truncate(!resume)
+      .open(path)?;
+
+    let wtr = WriterBuilder::new()
+      .has_headers(!has_header)
+      .from_writer(file);
+
+    info!("Saving completed simulations to checkpoint {}", path);
+    Ok(Checkpoint { wtr, settings })
+  }
+
+  /// Reads results and their seeds saved by a previous run, a missing
+  /// file has no results. Fails if the results were saved with other
+  /// settings
+  pub fn load(path: &str, settings: &RunSettings)
+    -> Result<Vec<(String, u64, SimulationResult)>> {
+    if !Path::new(path).exists() {
+      return Ok(Vec::new());
+    }
+
+    let mut rdr = Reader::from_path(path)?;
+    let mut results = Vec::new();
+    for record in rdr.deserialize() {
+      let record: CheckpointRecord = record?;
+      let saved = RunSettings {
+        cost: CostModel {
+          memory_ns: record.memory_ns,
+          fault_ns: record.fault_ns,
+          write_back_ns: record.write_back_ns,
+        },
+        prefetcher: record.prefetcher,
+        prefetch_degree: record.prefetch_degree,
+        nru_interval: record.nru_interval,
+      };
+      if saved != *settings {
+        return Err(Error::from(format!("Checkpoint {} was saved with different settings \
+          ({} instead of {}), run without --resume to start over", path, saved, settings)));
+      }
+
+      results.push((record.algorithm, record.seed, SimulationResult {
+        table_size: record.table_size,
+        hit_rate: record.hit_rate,
+        elapsed_secs: record.elapsed_secs,
+        effective_access_ns: record.effective_access_ns,
+        total_ns: record.total_ns,
+        demand_faults: record.demand_faults,
+        prefetch_used: record.prefetch_used,
+        prefetch_wasted: record.prefetch_wasted,
+        runs: 1,
+        hit_rate_stddev: 0.0,
+      }));
+    }
+
+    Ok(results)
+  }
+
+  /// Saves a completed simulation, flushed immediately so it survives the
+  /// program being stopped
+  pub fn write(&mut self, algorithm: &str, seed: u64, result: &SimulationResult)
+    -> Result<()> {
+    self.wtr.serialize(CheckpointRecord {
+      algorithm: algorithm.to_string(),
+      seed,
+      table_size: result.table_size,
+      hit_rate: result.hit_rate,
+      elapsed_secs: result.elapsed_secs,
+      effective_access_ns: result.effective_access_ns,
+      total_ns: result.total_ns,
+      demand_faults: result.demand_faults,
+      prefetch_used: result.prefetch_used,
+      prefetch_wasted: result.prefetch_wasted,
+      memory_ns: self.settings.cost.memory_ns,
+      fault_ns: self.settings.cost.fault_ns,
+      write_back_ns: self.settings.cost.write_back_ns,
+      prefetcher: self.settings.prefetcher.clone(),
+      prefetch_degree: self.settings.prefetch_degree,
+      nru_interval: self.settings.nru_interval,
+    })?;
+    self.wtr.flush()?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,222 @@
+use csv::Writer;
+use error::Result;
+use model::algorithms::Outcome;
+use model::simulation::Simulation;
+use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Arc;
+use util;
+
+/// Options for comparing two algorithms
+pub struct DiffOptions<'a> {
+  pub input: Option<&'a str>,
+  pub output: Option<&'a str>,
+  pub format: &'a str,
+  pub table_size: usize,
+  pub algorithm_a: &'a str,
+  pub algorithm_b: &'a str,
+  /// Max number of divergent requests shown in text output
+  pub limit: usize,
+}
+
+/// A page request that was a hit for one algorithm and a fault for the other
+#[derive(Debug, Serialize)]
+pub struct Divergence {
+  /// Position of the request in the trace, starting at 0
+  pub request: u64,
+  /// Requested page number
+  pub page: u64,
+  /// Either `hit` or `fault`
+  pub a_result: &'static str,
+  pub b_result: &'static str,
+  /// Page replaced by the algorithm that faulted
+  pub evicted: Option<u64>,
+}
+
+/// Number of divergent requests for a single page
+#[derive(Debug, Serialize)]
+pub struct PageDivergence {
+  pub page: u64,
+  pub a_hit_b_miss: u64,
+  pub a_miss_b_hit: u64,
+}
+
+/// Results of running two algorithms on the same trace
+#[derive(Debug, Serialize)]
+pub struct PolicyDiff {
+  pub algorithm_a: String,
+  pub algorithm_b: String,
+  pub table_size: usize,
+  pub requests: u64,
+  pub a_hits: u64,
+  pub b_hits: u64,
+  /// Requests that were a hit for A and a fault for B
+  pub a_hit_b_miss: u64,
+  /// Requests that were a fault for A and a hit for B
+  pub a_miss_b_hit: u64,
+  /// Pages with divergent requests, most divergent first
+  pub pages: Vec<PageDivergence>,
+  /// Every divergent request in trace order
+  pub divergences: Vec<Divergence>,
+}
+
+fn result_name(outcome: Outcome) -> &'static str {
+  if outcome.is_fault() { "fault" } else { "hit" }
+}
+
+impl PolicyDiff {
+  /// Runs both algorithms in lockstep over the requests
+  pub fn new(requests: &[u64], table_size: usize, algorithm_a: &str, algorithm_b: &str)
+    -> Result<Self> {
+    // only needed for optimal
+    let future = Arc::new(requests.to_vec());
+    let mut sim_a = Simulation::new(table_size, algorithm_a, Some(future.clone()))?;
+    let mut sim_b = Simulation::new(table_size, algorithm_b, Some(future))?;
+
+    let mut divergences = Vec::new();
+    let mut pages: HashMap<u64, PageDivergence> = HashMap::new();
+
+    for (i, &page) in requests.iter().enumerate() {
+      let a = sim_a.request(page)?;
+      let b = sim_b.request(page)?;
+
+      if a.is_fault() == b.is_fault() {
+        continue;
+      }
+
+      let entry = pages.entry(page).or_insert(PageDivergence {
+        page,
+        a_hit_b_miss: 0,
+        a_miss_b_hit: 0,
+      });
+
+      let faulted = if b.is_fault() {
+        entry.a_hit_b_miss += 1;
+        b
+      } else {
+        entry.a_miss_b_hit += 1;
+        a
+      };
+
+      let evicted = match faulted {
+        Outcome::Fault { evicted, .. } => evicted,
+        Outcome::Hit => None,
+      };
+
+      divergences.push(Divergence {
+        request: i as u64,
+        page,
+        a_result: result_name(a),
+        b_result: result_name(b),
+        evicted,
+      });
+    }
+
+    let mut pages: Vec<PageDivergence> = pages.into_values().collect();
+    pages.sort_by(|a, b| (b.a_hit_b_miss + b.a_miss_b_hit)
+      .cmp(&(a.a_hit_b_miss + a.a_miss_b_hit))
+      .then(a.page.cmp(&b.page)));
+
+    Ok(PolicyDiff {
+      algorithm_a: algorithm_a.to_string(),
+      algorithm_b: algorithm_b.to_string(),
+      table_size,
+      requests: requests.len() as u64,
+      a_hits: sim_a.num_requests() - sim_a.num_misses(),
+      b_hits: sim_b.num_requests() - sim_b.num_misses(),
+      a_hit_b_miss: pages.iter().map(|x| x.a_hit_b_miss).sum(),
+      a_miss_b_hit: pages.iter().map(|x| x.a_miss_b_hit).sum(),
+      pages,
+      divergences,
+    })
+  }
+
+  fn hit_rate(&self, hits: u64) -> f64 {
+    hits as f64 / self.requests as f64
+  }
+
+  /// Writes a human readable summary with up to `limit` divergent requests
+  /// and pages
+  pub fn write_text<W: Write>(&self, out: &mut W, limit: usize) -> Result<()> {
+    let a = self.algorithm_a.to_uppercase();
+    let b = self.algorithm_b.to_uppercase();
+
+    writeln!(out, "{} vs {}, table size {}, {} requests",
+      a, b, self.table_size, self.requests)?;
+    writeln!(out, "{:<18}{:<12}{}", "", a, b)?;
+    writeln!(out, "{:<18}{:<12}{}", "Hits", self.a_hits, self.b_hits)?;
+    writeln!(out, "{:<18}{:<12.5}{:.5}", "Hit rate",
+      self.hit_rate(self.a_hits), self.hit_rate(self.b_hits))?;
+    writeln!(out)?;
+    let a_hit_b_miss = format!("{} hit / {} miss", a, b);
+    let a_miss_b_hit = format!("{} miss / {} hit", a, b);
+    writeln!(out, "{:<24}{}", a_hit_b_miss, self.a_hit_b_miss)?;
+    writeln!(out, "{:<24}{}", a_miss_b_hit, self.a_miss_b_hit)?;
+
+    writeln!(out, "\nPages with most divergent requests ({} of {})",
+      self.pages.len().min(limit), self.pages.len())?;
+    writeln!(out, "  {:<12}{:<24}{}", "page", a_hit_b_miss, a_miss_b_hit)?;
+    for x in self.pages.iter().take(limit) {
+      writeln!(out, "  {:<12}{:<24}{}", x.page, x.a_hit_b_miss, x.a_miss_b_hit)?;
+    }
+
+    writeln!(out, "\nDivergent requests ({} of {})",
+      self.divergences.len().min(limit), self.divergences.len())?;
+    writeln!(out, "  {:<12}{:<12}{:<8}{:<8}evicted", "request", "page", a, b)?;
+    for x in self.divergences.iter().take(limit) {
+      let evicted = x.evicted.map(|x| x.to_string()).unwrap_or_else(|| "-".to_string());
+      writeln!(out, "  {:<12}{:<12}{:<8}{:<8}{}",
+        x.request, x.page, x.a_result, x.b_result, evicted)?;
+    }
+
+    Ok(())
+  }
+
+  /// Writes the summary and all divergent requests as a single JSON object
+  pub fn write_json<W: Write>(&self, out: &mut W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, self)?;
+    writeln!(out)?;
+
+    Ok(())
+  }
+
+  /// Writes all divergent requests as csv, one row per request
+  pub fn write_csv<W: Write>(&self, out: W) -> Result<()> {
+    let mut wtr = Writer::from_writer(out);
+    for x in &self.divergences {
+      wtr.serialize(x)?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+  }
+}
+
+/// Compares two algorithms and writes the differences to the output file
+/// or stdout
+pub fn diff(options: DiffOptions) -> Result<()> {
+  let requests = util::read_requests(options.input)?;
+  let result = PolicyDiff::new(&requests, options.table_size,
+    options.algorithm_a, options.algorithm_b)?;
+
+  info!("Found {} divergent requests", result.divergences.len());
+
+  let mut out: Box<dyn Write> = match options.output {
+    Some(output) => Box::new(File::create(output)?),
+    None => Box::new(io::stdout()),
+  };
+
+  match options.format {
+    "json" => result.write_json(&mut out)?,
+    "csv" => result.write_csv(&mut out)?,
+    _ => result.write_text(&mut out, options.limit)?,
+  }
+
+  if let Some(output) = options.output {
+    info!("Saved algorithm comparison to {}", output);
+  }
+
+  Ok(())
+}
@@ -1,10 +1,12 @@
 use csv::Error as CsvError;
 use log::SetLoggerError;
+use serde_json::Error as JsonError;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::result::Result as StdResult;
 use std::io::Error as IoError;
 use std::num::ParseIntError;
+use toml::de::Error as TomlError;
 
 /// Common result type used throughout the program.
 pub type Result<T> = StdResult<T, Error>;
@@ -15,6 +17,8 @@ pub type Result<T> = StdResult<T, Error>;
 pub enum Error {
   /// A custom process error from string
   Paging(String),
+  /// An unknown algorithm, or an algorithm missing what it needs to run
+  Algorithm(String),
   /// A error from parsing an int
   ParseInt(ParseIntError),
   /// A `std::io` module error.
@@ -23,6 +27,10 @@ pub enum Error {
   SetLogger(SetLoggerError),
   /// A `csv` crate error
   Csv(CsvError),
+  /// A `serde_json` crate error
+  Json(JsonError),
+  /// A `toml` crate error from reading a config file
+  Toml(TomlError),
 }
 
 impl<'a> From<&'a str> for Error {
@@ -31,6 +39,12 @@ impl<'a> From<&'a str> for Error {
   }
 }
 
+impl From<String> for Error {
+  fn from(err: String) -> Error {
+    Error::Paging(err)
+  }
+}
+
 impl From<ParseIntError> for Error {
   fn from(err: ParseIntError) -> Error {
     Error::ParseInt(err)
@@ -55,26 +69,31 @@ impl From<CsvError> for Error {
   }
 }
 
+impl From<JsonError> for Error {
+  fn from(err: JsonError) -> Error {
+    Error::Json(err)
+  }
+}
+
+impl From<TomlError> for Error {
+  fn from(err: TomlError) -> Error {
+    Error::Toml(err)
+  }
+}
+
 impl Display for Error {
   fn fmt(&self, f: &mut Formatter) -> FmtResult {
     match *self {
       Error::Paging(ref inner) => inner.fmt(f),
+      Error::Algorithm(ref inner) => inner.fmt(f),
       Error::ParseInt(ref inner) => inner.fmt(f),
       Error::Io(ref inner) => inner.fmt(f),
       Error::SetLogger(ref inner) => inner.fmt(f),
       Error::Csv(ref inner) => inner.fmt(f),
+      Error::Json(ref inner) => inner.fmt(f),
+      Error::Toml(ref inner) => inner.fmt(f),
     }
   }
 }
 
-impl StdError for Error {
-  fn description(&self) -> &str {
-    match *self {
-      Error::Paging(ref inner) => inner,
-      Error::ParseInt(ref inner) => inner.description(),
-      Error::Io(ref inner) => inner.description(),
-      Error::SetLogger(ref inner) => inner.description(),
-      Error::Csv(ref inner) => inner.description(),
-    }
-  }
-}
\ No newline at end of file
+impl StdError for Error {}
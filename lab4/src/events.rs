@@ -0,0 +1,94 @@
+use csv::Writer;
+use error::Result;
+use model::algorithms::Outcome;
+use serde::Serialize;
+use serde_json;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Result of a single page request, flattened to be written as csv or json
+#[derive(Debug, Serialize)]
+pub struct Event<P = u64> {
+  /// Position of the request in the trace, starting at 0
+  pub request: u64,
+  /// Requested page number
+  pub page: P,
+  /// Either `hit` or `fault`
+  pub result: &'static str,
+  /// Page replaced by a page fault, empty if the frame was free
+  pub evicted: Option<P>,
+  /// Frame the page was loaded into on a page fault
+  pub frame: Option<usize>,
+}
+
+impl<P> Event<P> {
+  pub fn new(request: u64, page: P, outcome: Outcome<P>) -> Self {
+    match outcome {
+      Outcome::Hit => Event {
+        request,
+        page,
+        result: "hit",
+        evicted: None,
+        frame: None,
+      },
+      Outcome::Fault { evicted, frame } => Event {
+        request,
+        page,
+        result: "fault",
+        evicted,
+        frame: Some(frame),
+      },
+    }
+  }
+}
+
+/// Writes page request events to a file as json lines or csv
+pub enum EventWriter {
+  Jsonl(BufWriter<File>),
+  Csv(Box<Writer<File>>),
+}
+
+impl EventWriter {
+  /// Creates an events file for a single simulation, the algorithm name and
+  /// table size are inserted into the file name:
+  /// events.jsonl -> events.lru.10.jsonl
+  pub fn create(output: &str, format: &str, algorithm: &str, table_size: usize)
+    -> Result<Self> {
+    let extension = format!(".{}", format);
+    let output = format!("{}.{}.{}{}",
+      output.replace(&extension, ""), algorithm, table_size, extension);
+
+    let file = File::create(&output)?;
+    info!("Writing page request events to {}", &output);
+
+    let writer = match format {
+      "csv" => EventWriter::Csv(Box::new(Writer::from_writer(file))),
+      _ => EventWriter::Jsonl(BufWriter::new(file)),
+    };
+
+    Ok(writer)
+  }
+
+  /// Writes a single event
+  pub fn write<P: Serialize>(&mut self, event: &Event<P>) -> Result<()> {
+    match *self {
+      EventWriter::Jsonl(ref mut wtr) => {
+        serde_json::to_writer(&mut *wtr, event)?;
+        writeln!(wtr)?;
+      },
+      EventWriter::Csv(ref mut wtr) => wtr.serialize(event)?,
+    }
+
+    Ok(())
+  }
+
+  /// Flushes buffered events to the file
+  pub fn flush(&mut self) -> Result<()> {
+    match *self {
+      EventWriter::Jsonl(ref mut wtr) => wtr.flush()?,
+      EventWriter::Csv(ref mut wtr) => wtr.flush()?,
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,497 @@
+use chrono::Local;
+use csv::Writer;
+use error::{Error, Result};
+use import::TraceFormat;
+use model::algorithms::{self, AlgorithmParams};
+use model::prefetch::Prefetcher;
+use model::simulation::{CostModel, Simulation, SimulationResult, TraceEntry};
+use parking_lot::Mutex;
+use progress::Progress;
+use serde_json;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use threadpool::Builder;
+use toml;
+use util;
+
+const ALGORITHMS: [&str; 7] = ["fifo", "lru", "second_chance", "sc", "optimal", "random", "nru"];
+const PREFETCHERS: [&str; 4] = ["none", "sequential", "stride", "cluster"];
+const FORMATS: [&str; 4] = ["plain", "spc", "msr", "twitter"];
+
+/// Number of page requests simulated between progress updates
+const PROGRESS_CHUNK: usize = 4096;
+
+/// Options for running an experiment
+pub struct ExperimentOptions<'a> {
+  /// Path of the TOML experiment file
+  pub config: &'a str,
+  /// Number of threads to use, defaults to the number of CPUs
+  pub jobs: Option<usize>,
+  pub progress: &'a str,
+}
+
+fn default_format() -> String {
+  "plain".to_string()
+}
+
+fn default_block_size() -> String {
+  "4K".to_string()
+}
+
+/// A trace of the experiment and how to read it
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TraceConfig {
+  /// Relative paths are relative to the experiment file
+  path: String,
+  #[serde(default = "default_format")]
+  format: String,
+  #[serde(default = "default_block_size")]
+  block_size: String,
+  #[serde(default)]
+  ops: Option<Vec<String>>,
+}
+
+/// Parameters to run every combination of, only for the algorithms that
+/// use them
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct ParameterConfig {
+  /// Seeds of random and nru
+  seeds: Vec<u64>,
+  /// Intervals between clearing referenced bits of nru
+  nru_intervals: Vec<u64>,
+  /// Prefetchers, `none` for demand paging only
+  prefetchers: Vec<String>,
+  /// Degrees of the prefetchers other than `none`
+  prefetch_degrees: Vec<u64>,
+}
+
+impl Default for ParameterConfig {
+  fn default() -> Self {
+    ParameterConfig {
+      seeds: vec![0],
+      nru_intervals: vec![100],
+      prefetchers: vec!["none".to_string()],
+      prefetch_degrees: vec![4],
+    }
+  }
+}
+
+/// An experiment file, a matrix of traces × algorithms × sizes × parameters
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct ExperimentConfig {
+  /// Defaults to the name of the experiment file
+  name: Option<String>,
+  /// Results file, defaults to the experiment file with `.results.csv`.
+  /// Relative paths are relative to the experiment file
+  output: Option<String>,
+  traces: Vec<TraceConfig>,
+  algorithms: Vec<String>,
+  /// Table sizes in the same format as `--sizes`, ex: `10..500:10`
+  sizes: String,
+  #[serde(default)]
+  parameters: ParameterConfig,
+  #[serde(default)]
+  cost: CostModel,
+}
+
+impl ExperimentConfig {
+  /// Checks every value that is validated by clap on the command line
+  fn validate(&self) -> Result<()> {
+    let invalid = |message: String| Err(Error::Paging(message));
+
+    if self.traces.is_empty() || self.algorithms.is_empty() {
+      return invalid("An experiment needs at least one trace and algorithm".to_string());
+    }
+
+    for trace in &self.traces {
+      if !FORMATS.contains(&trace.format.as_str()) {
+        return invalid(format!("Invalid trace format {}, expected one of {}",
+          trace.format, FORMATS.join(", ")));
+      }
+      util::validate_capacity(trace.block_size.clone())?;
+    }
+
+    for algorithm in &self.algorithms {
+      if !ALGORITHMS.contains(&algorithm.as_str()) {
+        return invalid(format!("Invalid algorithm {}, expected one of {}",
+          algorithm, ALGORITHMS.join(", ")));
+      }
+    }
+
+    for prefetcher in &self.parameters.prefetchers {
+      if !PREFETCHERS.contains(&prefetcher.as_str()) {
+        return invalid(format!("Invalid prefetcher {}, expected one of {}",
+          prefetcher, PREFETCHERS.join(", ")));
+      }
+    }
+
+    let parameters = &self.parameters;
+    if parameters.seeds.is_empty() || parameters.nru_intervals.is_empty()
+      || parameters.prefetchers.is_empty() || parameters.prefetch_degrees.is_empty() {
+      return invalid("Parameter lists cannot be empty, leave them out to use the defaults"
+        .to_string());
+    }
+
+    Ok(())
+  }
+}
+
+/// A single simulation of the experiment matrix
+#[derive(Clone, Debug)]
+struct Run {
+  /// Index of the trace in the experiment file
+  trace: usize,
+  algorithm: String,
+  table_size: usize,
+  /// Parameters are only set if the algorithm uses them
+  seed: Option<u64>,
+  nru_interval: Option<u64>,
+  prefetch: Option<String>,
+  prefetch_degree: Option<u64>,
+}
+
+impl Run {
+  fn description(&self, traces: &[TraceConfig]) -> String {
+    let mut description = format!("{} with table size {} on {}",
+      self.algorithm.to_uppercase(), self.table_size, traces[self.trace].path);
+    if let Some(seed) = self.seed {
+      description.push_str(&format!(", seed {}", seed));
+    }
+    if let Some(interval) = self.nru_interval {
+      description.push_str(&format!(", nru interval {}", interval));
+    }
+    if let (Some(ref prefetch), Some(degree)) = (&self.prefetch, self.prefetch_degree) {
+      description.push_str(&format!(", {} prefetch degree {}", prefetch, degree));
+    }
+
+    description
+  }
+}
+
+/// Lists every combination of the experiment in the order of the file.
+/// Optimal is only run without prefetching
+fn experiment_runs(config: &ExperimentConfig, table_sizes: &[usize]) -> Vec<Run> {
+  let parameters = &config.parameters;
+
+  // prefetcher and degree pairs, none has no degree
+  let mut prefetchers = Vec::new();
+  for prefetcher in &parameters.prefetchers {
+    if prefetcher == "none" {
+      prefetchers.push((None, None));
+      continue;
+    }
+    for &degree in &parameters.prefetch_degrees {
+      prefetchers.push((Some(prefetcher.clone()), Some(degree)));
+    }
+  }
+
+  let mut runs = Vec::new();
+  for trace in 0..config.traces.len() {
+    for algorithm in &config.algorithms {
+      let seeds: Vec<Option<u64>> = if algorithms::is_randomized(algorithm) {
+        parameters.seeds.iter().cloned().map(Some).collect()
+      } else {
+        vec![None]
+      };
+      let intervals: Vec<Option<u64>> = if algorithm == "nru" {
+        parameters.nru_intervals.iter().cloned().map(Some).collect()
+      } else {
+        vec![None]
+      };
+
+      for &table_size in table_sizes {
+        for &seed in &seeds {
+          for &nru_interval in &intervals {
+            for &(ref prefetch, prefetch_degree) in &prefetchers {
+              if algorithm == "optimal" && prefetch.is_some() {
+                continue;
+              }
+
+              runs.push(Run {
+                trace,
+                algorithm: algorithm.clone(),
+                table_size,
+                seed,
+                nru_interval,
+                prefetch: prefetch.clone(),
+                prefetch_degree,
+              });
+            }
+          }
+        }
+      }
+    }
+  }
+
+  runs
+}
+
+/// A parsed trace shared by its simulations
+struct LoadedTrace {
+  entries: Arc<Vec<TraceEntry>>,
+  /// Page requests without directives, only read by optimal
+  future: Arc<Vec<u64>>,
+}
+
+/// Reads a trace of the experiment with its format
+fn load_trace(path: &Path, trace: &TraceConfig) -> Result<LoadedTrace> {
+  info!("Reading page accesses from file {}", path.display());
+  let format = TraceFormat::new(&trace.format, util::parse_bytes(&trace.block_size)?,
+    trace.ops.clone());
+
+  let mut entries = Vec::new();
+  for line in BufReader::new(File::open(path)?).lines() {
+    format.parse_line(&line?, &mut entries);
+  }
+  format.finish();
+
+  let future = entries
+    .iter()
+    .filter_map(|x| match *x {
+      TraceEntry::Request(ref request) => Some(request.page),
+      _ => None,
+    })
+    .collect();
+
+  Ok(LoadedTrace {
+    entries: Arc::new(entries),
+    future: Arc::new(future),
+  })
+}
+
+/// Runs a single simulation of the matrix
+fn run_simulation(run: &Run, trace: &LoadedTrace, cost: &CostModel, bar: &Progress)
+  -> Result<SimulationResult> {
+  let start = Instant::now();
+  let params = AlgorithmParams {
+    seed: run.seed.unwrap_or(0),
+    nru_interval: run.nru_interval.unwrap_or(100),
+  };
+  let future = if run.algorithm == "optimal" { Some(trace.future.clone()) } else { None };
+
+  let mut sim = Simulation::with_params(run.table_size, &run.algorithm, &params, future)?;
+  if let (Some(ref prefetch), Some(degree)) = (&run.prefetch, run.prefetch_degree) {
+    sim.set_prefetcher(Some(Prefetcher::new(prefetch, degree)));
+  }
+
+  for chunk in trace.entries.chunks(PROGRESS_CHUNK) {
+    for entry in chunk {
+      sim.run_entry(*entry)?;
+    }
+    bar.advance(chunk.len() as u64);
+  }
+
+  Ok(sim.result(run.table_size, cost, start.elapsed().as_secs_f64()))
+}
+
+/// A single value of the tidy results file
+#[derive(Serialize)]
+struct TidyRecord<'a> {
+  trace: &'a str,
+  algorithm: &'a str,
+  table_size: usize,
+  seed: Option<u64>,
+  nru_interval: Option<u64>,
+  prefetch: Option<&'a str>,
+  prefetch_degree: Option<u64>,
+  metric: &'a str,
+  value: f64,
+}
+
+/// Writes results in long format, one row per run and metric
+fn write_results(path: &Path, config: &ExperimentConfig,
+  results: &[(Run, SimulationResult)]) -> Result<()> {
+  let mut wtr = Writer::from_path(path)?;
+
+  for (run, result) in results {
+    let metrics = [
+      ("hit_rate", result.hit_rate),
+      ("effective_access_ns", result.effective_access_ns),
+      ("total_ns", result.total_ns),
+      ("demand_faults", result.demand_faults as f64),
+      ("prefetch_used", result.prefetch_used as f64),
+      ("prefetch_wasted", result.prefetch_wasted as f64),
+      ("elapsed_secs", result.elapsed_secs),
+    ];
+
+    for &(metric, value) in metrics.iter() {
+      wtr.serialize(TidyRecord {
+        trace: &config.traces[run.trace].path,
+        algorithm: &run.algorithm,
+        table_size: run.table_size,
+        seed: run.seed,
+        nru_interval: run.nru_interval,
+        prefetch: run.prefetch.as_deref(),
+        prefetch_degree: run.prefetch_degree,
+        metric,
+        value,
+      })?;
+    }
+  }
+  wtr.flush()?;
+
+  Ok(())
+}
+
+/// A trace as it was read, for the manifest
+#[derive(Serialize)]
+struct TraceManifest<'a> {
+  #[serde(flatten)]
+  config: &'a TraceConfig,
+  /// Number of page requests after splitting blocks and filtering
+  requests: usize,
+}
+
+/// Everything needed to reproduce or interpret the results file
+#[derive(Serialize)]
+struct Manifest<'a> {
+  name: &'a str,
+  config: &'a str,
+  version: &'a str,
+  started: String,
+  finished: String,
+  elapsed_secs: f64,
+  results: String,
+  traces: Vec<TraceManifest<'a>>,
+  algorithms: &'a [String],
+  table_sizes: &'a [usize],
+  parameters: &'a ParameterConfig,
+  cost: CostModel,
+  runs: usize,
+  completed: usize,
+  /// Runs that failed and their errors
+  failed: Vec<String>,
+}
+
+/// Runs every simulation of an experiment file on the threadpool, writes
+/// the tidy results file and a manifest next to it
+pub fn run_experiment(options: ExperimentOptions) -> Result<()> {
+  let started = Local::now();
+  let start = Instant::now();
+
+  let config: ExperimentConfig = toml::from_str(&fs::read_to_string(options.config)?)?;
+  config.validate()?;
+  let table_sizes = util::parse_table_sizes(&config.sizes)?;
+
+  // paths in the experiment file are relative to it
+  let config_path = Path::new(options.config);
+  let base = config_path.parent().unwrap_or_else(|| Path::new(""));
+  let output = match config.output {
+    Some(ref output) => base.join(output),
+    None => config_path.with_extension("results.csv"),
+  };
+  let manifest_path = output.with_extension("manifest.json");
+  let name = config.name.clone().unwrap_or_else(|| config_path.file_stem()
+    .map(|x| x.to_string_lossy().to_string())
+    .unwrap_or_default());
+
+  let mut traces = Vec::new();
+  for trace in &config.traces {
+    let loaded = load_trace(&base.join(&trace.path), trace)
+      .map_err(|e| Error::Paging(format!("Failed to read trace {}: {}", trace.path, e)))?;
+    traces.push(Arc::new(loaded));
+  }
+
+  let runs = experiment_runs(&config, &table_sizes);
+  info!("Running experiment {} with {} simulations", name, runs.len());
+
+  let results = Arc::new(Mutex::new(Vec::new()));
+  let failed = Arc::new(Mutex::new(Vec::new()));
+
+  // build threadpool, # threads = cpu count unless set
+  let mut builder = Builder::new()
+    .thread_name("simulation_worker".into());
+  if let Some(num_threads) = options.jobs {
+    builder = builder.num_threads(num_threads);
+  }
+  let pool = builder.build();
+  info!("Using {} threads for concurrent simulations", pool.max_count());
+
+  // average length of the traces, only used for the request rate
+  let num_requests = traces.iter().map(|x| x.entries.len() as u64).sum::<u64>()
+    / traces.len() as u64;
+  let bar = Arc::new(Progress::new(options.progress, runs.len() as u64, num_requests));
+
+  // largest table sizes first like a range of simulations, stable sort
+  // keeps the matrix order for the same size
+  let mut order: Vec<usize> = (0..runs.len()).collect();
+  order.sort_by_key(|&i| std::cmp::Reverse(runs[i].table_size));
+
+  for index in order {
+    let run = runs[index].clone();
+    let trace = traces[run.trace].clone();
+    let description = run.description(&config.traces);
+    let cost = config.cost;
+    let results = results.clone();
+    let failed = failed.clone();
+    let bar = bar.clone();
+
+    pool.execute(move || {
+      bar.start(&run.algorithm, run.table_size);
+      match run_simulation(&run, &trace, &cost, &bar) {
+        Ok(result) => {
+          bar.complete(&run.algorithm, &result);
+          results.lock().push((index, run, result));
+        },
+        Err(e) => {
+          error!("Failed simulation {}: {}", description, e);
+          failed.lock().push(format!("{}: {}", description, e));
+        },
+      }
+    });
+  }
+
+  pool.join();
+  bar.finish();
+
+  // keep the order of the matrix, not the order simulations finished in
+  let mut results = Arc::try_unwrap(results).unwrap().into_inner();
+  results.sort_by_key(|x| x.0);
+  let results: Vec<(Run, SimulationResult)> = results.into_iter()
+    .map(|(_, run, result)| (run, result))
+    .collect();
+
+  write_results(&output, &config, &results)?;
+  info!("Saved experiment results to {}", output.display());
+
+  let manifest = Manifest {
+    name: &name,
+    config: options.config,
+    version: env!("CARGO_PKG_VERSION"),
+    started: started.to_rfc3339(),
+    finished: Local::now().to_rfc3339(),
+    elapsed_secs: start.elapsed().as_secs_f64(),
+    results: output.display().to_string(),
+    traces: config.traces.iter().zip(traces.iter())
+      .map(|(config, trace)| TraceManifest {
+        config,
+        requests: trace.future.len(),
+      })
+      .collect(),
+    algorithms: &config.algorithms,
+    table_sizes: &table_sizes,
+    parameters: &config.parameters,
+    cost: config.cost,
+    runs: runs.len(),
+    completed: results.len(),
+    failed: failed.lock().clone(),
+  };
+
+  let mut file = File::create(&manifest_path)?;
+  serde_json::to_writer_pretty(&mut file, &manifest)?;
+  info!("Saved experiment manifest to {}", manifest_path.display());
+
+  let num_failed = manifest.failed.len();
+  if num_failed > 0 {
+    return Err(Error::Paging(format!("{} of {} simulations failed, see the manifest",
+      num_failed, runs.len())));
+  }
+
+  Ok(())
+}
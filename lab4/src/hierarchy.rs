@@ -0,0 +1,197 @@
+use csv::Writer;
+use error::Result;
+use model::hierarchy::{Hierarchy, HierarchyOptions, Level};
+use serde_json;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Arc;
+use util;
+
+/// Options for simulating a memory hierarchy
+pub struct HierarchyRunOptions<'a> {
+  pub input: Option<&'a str>,
+  pub output: Option<&'a str>,
+  pub format: &'a str,
+  pub hierarchy: HierarchyOptions<'a>,
+}
+
+/// Requests and hits of a single level
+#[derive(Debug, Serialize)]
+pub struct LevelReport {
+  /// One of `tlb`, `memory`, `secondary` or `backing`
+  pub level: &'static str,
+  /// Not set for the backing store, which always has the page
+  pub algorithm: Option<String>,
+  pub size: Option<usize>,
+  pub latency_ns: f64,
+  /// Number of requests that reached this level
+  pub requests: u64,
+  pub hits: u64,
+  pub hit_rate: f64,
+}
+
+impl LevelReport {
+  fn new(name: &'static str, level: &Level) -> Self {
+    LevelReport {
+      level: name,
+      algorithm: Some(level.algorithm.clone()),
+      size: Some(level.size),
+      latency_ns: level.latency_ns,
+      requests: level.requests(),
+      hits: level.hits(),
+      hit_rate: rate(level.hits(), level.requests()),
+    }
+  }
+}
+
+fn rate(count: u64, total: u64) -> f64 {
+  if total == 0 {
+    return 0.0;
+  }
+
+  count as f64 / total as f64
+}
+
+/// Results of running a trace through a memory hierarchy
+#[derive(Debug, Serialize)]
+pub struct HierarchyReport {
+  pub requests: u64,
+  /// None if there is no TLB
+  pub tlb_hit_rate: Option<f64>,
+  /// Fraction of requests that were not in the page frames
+  pub page_fault_rate: f64,
+  /// Average time of a page request in nanoseconds
+  pub effective_access_ns: f64,
+  pub total_ns: f64,
+  /// Levels in the order they are checked
+  pub levels: Vec<LevelReport>,
+}
+
+impl HierarchyReport {
+  /// Runs every request through a new hierarchy
+  pub fn new(requests: &[u64], options: &HierarchyOptions) -> Result<Self> {
+    // only needed for optimal
+    let mut hierarchy = Hierarchy::new(options, Some(Arc::new(requests.to_vec())))?;
+    for &page in requests {
+      hierarchy.request(page)?;
+    }
+
+    let mut levels = Vec::new();
+    if let Some(ref tlb) = hierarchy.tlb {
+      levels.push(LevelReport::new("tlb", tlb));
+    }
+    levels.push(LevelReport::new("memory", &hierarchy.memory));
+    if let Some(ref secondary) = hierarchy.secondary {
+      levels.push(LevelReport::new("secondary", secondary));
+    }
+
+    let backing_requests = hierarchy.backing_requests();
+    levels.push(LevelReport {
+      level: "backing",
+      algorithm: None,
+      size: None,
+      latency_ns: hierarchy.fault_ns,
+      requests: backing_requests,
+      hits: backing_requests,
+      hit_rate: rate(backing_requests, backing_requests),
+    });
+
+    let memory = &hierarchy.memory;
+    Ok(HierarchyReport {
+      requests: hierarchy.num_requests(),
+      tlb_hit_rate: hierarchy.tlb.as_ref().map(|x| rate(x.hits(), x.requests())),
+      page_fault_rate: rate(memory.requests() - memory.hits(), memory.requests()),
+      effective_access_ns: hierarchy.effective_access_ns(),
+      total_ns: hierarchy.total_ns(),
+      levels,
+    })
+  }
+
+  /// Writes a human readable summary and a table of levels
+  pub fn write_text<W: Write>(&self, out: &mut W) -> Result<()> {
+    writeln!(out, "{:<22}{}", "Requests", self.requests)?;
+    if let Some(tlb_hit_rate) = self.tlb_hit_rate {
+      writeln!(out, "{:<22}{:.5}", "TLB hit rate", tlb_hit_rate)?;
+    }
+    writeln!(out, "{:<22}{:.5}", "Page fault rate", self.page_fault_rate)?;
+    writeln!(out, "{:<22}{:.3} ns", "Effective access time", self.effective_access_ns)?;
+    writeln!(out, "{:<22}{:.3} ms", "Total time", self.total_ns / 1e6)?;
+
+    writeln!(out, "\nLevels")?;
+    writeln!(out, "  {:<12}{:<16}{:<10}{:<14}{:<12}{:<12}hit rate",
+      "level", "algorithm", "size", "latency ns", "requests", "hits")?;
+    for x in &self.levels {
+      let algorithm = x.algorithm.as_deref().unwrap_or("-");
+      let size = x.size.map(|x| x.to_string()).unwrap_or_else(|| "-".to_string());
+      writeln!(out, "  {:<12}{:<16}{:<10}{:<14}{:<12}{:<12}{:.5}",
+        x.level, algorithm, size, x.latency_ns, x.requests, x.hits, x.hit_rate)?;
+    }
+
+    Ok(())
+  }
+
+  /// Writes the summary and all levels as a single JSON object
+  pub fn write_json<W: Write>(&self, out: &mut W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, self)?;
+    writeln!(out)?;
+
+    Ok(())
+  }
+
+  /// Writes the summary and all levels as long format csv with the columns
+  /// `stat,key,value`, ex: `tlb,hit_rate,0.98`
+  pub fn write_csv<W: Write>(&self, out: W) -> Result<()> {
+    let mut wtr = Writer::from_writer(out);
+
+    wtr.write_record(["stat", "key", "value"])?;
+    wtr.write_record(["summary", "requests", &self.requests.to_string()])?;
+    if let Some(tlb_hit_rate) = self.tlb_hit_rate {
+      wtr.write_record(["summary", "tlb_hit_rate", &tlb_hit_rate.to_string()])?;
+    }
+    wtr.write_record(["summary", "page_fault_rate", &self.page_fault_rate.to_string()])?;
+    wtr.write_record(["summary", "effective_access_ns", &self.effective_access_ns.to_string()])?;
+    wtr.write_record(["summary", "total_ns", &self.total_ns.to_string()])?;
+
+    for x in &self.levels {
+      if let Some(ref algorithm) = x.algorithm {
+        wtr.write_record([x.level, "algorithm", algorithm])?;
+      }
+      if let Some(size) = x.size {
+        wtr.write_record([x.level, "size", &size.to_string()])?;
+      }
+      wtr.write_record([x.level, "latency_ns", &x.latency_ns.to_string()])?;
+      wtr.write_record([x.level, "requests", &x.requests.to_string()])?;
+      wtr.write_record([x.level, "hits", &x.hits.to_string()])?;
+      wtr.write_record([x.level, "hit_rate", &x.hit_rate.to_string()])?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+  }
+}
+
+/// Simulates a memory hierarchy and writes the results to the output file
+/// or stdout
+pub fn hierarchy(options: HierarchyRunOptions) -> Result<()> {
+  let requests = util::read_requests(options.input)?;
+  let report = HierarchyReport::new(&requests, &options.hierarchy)?;
+
+  info!("Effective access time {:.3} ns", report.effective_access_ns);
+
+  let mut out: Box<dyn Write> = match options.output {
+    Some(output) => Box::new(File::create(output)?),
+    None => Box::new(io::stdout()),
+  };
+
+  match options.format {
+    "json" => report.write_json(&mut out)?,
+    "csv" => report.write_csv(&mut out)?,
+    _ => report.write_text(&mut out)?,
+  }
+
+  if let Some(output) = options.output {
+    info!("Saved hierarchy results to {}", output);
+  }
+
+  Ok(())
+}
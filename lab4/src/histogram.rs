@@ -0,0 +1,138 @@
+use analyze::{self, Bucket, ExactHistogram, LogHistogram};
+use csv::Writer;
+use error::Result;
+use std::fs::File;
+use std::io::{self, Write};
+use util;
+
+/// Options for writing the histograms of a trace
+pub struct HistogramOptions<'a> {
+  pub input: Option<&'a str>,
+  pub output: Option<&'a str>,
+  /// Either `log` for log2 sized buckets or `exact` for every value
+  pub mode: &'a str,
+}
+
+/// A bucket of a histogram, written as a csv row
+#[derive(Debug, Serialize)]
+pub struct HistogramRow {
+  /// Either `reuse_distance` or `reference_gap`
+  pub histogram: &'static str,
+  /// Range of values counted, empty for first requests of a page
+  pub start: Option<u64>,
+  pub end: Option<u64>,
+  /// Number of requests in the bucket
+  pub count: u64,
+  /// Fraction of all requests with a value of at most `end`. For reuse
+  /// distances this is the hit rate of LRU with `end + 1` frames
+  pub cumulative: Option<f64>,
+}
+
+/// Buckets the values of every request, requests without a value are
+/// counted in a last row without a range
+pub fn histogram_rows(histogram: &'static str, values: &[Option<u64>], mode: &str)
+  -> Vec<HistogramRow> {
+  let mut log = LogHistogram::default();
+  let mut exact = ExactHistogram::default();
+  let mut cold = 0;
+  for value in values {
+    match (*value, mode) {
+      (Some(value), "exact") => exact.add(value),
+      (Some(value), _) => log.add(value),
+      (None, _) => cold += 1,
+    }
+  }
+
+  let buckets: Vec<Bucket> = match mode {
+    "exact" => exact.buckets(),
+    _ => log.buckets(),
+  };
+
+  // an empty trace has no fractions
+  let total = values.len() as f64;
+  let mut below = 0;
+  let mut rows: Vec<HistogramRow> = buckets
+    .into_iter()
+    .map(|bucket| {
+      below += bucket.count;
+      HistogramRow {
+        histogram,
+        start: Some(bucket.start),
+        end: Some(bucket.end),
+        count: bucket.count,
+        cumulative: if total > 0.0 { Some(below as f64 / total) } else { None },
+      }
+    })
+    .collect();
+
+  rows.push(HistogramRow {
+    histogram,
+    start: None,
+    end: None,
+    count: cold,
+    cumulative: None,
+  });
+  rows
+}
+
+/// Writes the reuse distance and inter-reference gap histograms of a trace
+/// as csv to the output file or stdout
+pub fn histogram(options: HistogramOptions) -> Result<()> {
+  let requests = util::read_requests(options.input)?;
+
+  let mut rows = histogram_rows("reuse_distance", &analyze::reuse_distances(&requests),
+    options.mode);
+  rows.extend(histogram_rows("reference_gap", &analyze::reference_gaps(&requests),
+    options.mode));
+
+  let out: Box<dyn Write> = match options.output {
+    Some(output) => Box::new(File::create(output)?),
+    None => Box::new(io::stdout()),
+  };
+
+  let mut wtr = Writer::from_writer(out);
+  for row in &rows {
+    wtr.serialize(row)?;
+  }
+  wtr.flush()?;
+
+  if let Some(output) = options.output {
+    info!("Saved histograms to {}", output);
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use model::simulation::Simulation;
+  use proptest::prelude::*;
+  use super::*;
+
+  proptest! {
+    #[test]
+    fn reuse_distance_histograms_predict_lru(trace in prop::collection::vec(0u64..12, 0..200)) {
+      let distances = analyze::reuse_distances(&trace);
+      for mode in &["exact", "log"] {
+        // lru with n frames hits the requests with a reuse distance below n
+        for row in histogram_rows("reuse_distance", &distances, mode) {
+          if let (Some(end), Some(cumulative)) = (row.end, row.cumulative) {
+            let mut sim = Simulation::new(end as usize + 1, "lru", None).unwrap();
+            for &page in &trace {
+              sim.request(page).unwrap();
+            }
+            let hits = trace.len() as u64 - sim.num_misses();
+            prop_assert!((cumulative - hits as f64 / trace.len() as f64).abs() < 1e-9);
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn empty_trace_has_no_fractions() {
+    let rows = histogram_rows("reuse_distance", &[], "log");
+    assert_eq!(rows.len(), 1);
+    assert_eq!((rows[0].count, rows[0].cumulative), (0, None));
+  }
+}
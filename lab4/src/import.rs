@@ -0,0 +1,301 @@
+use model::page::PageId;
+use model::simulation::{PageRequest, TraceEntry};
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use util;
+
+/// Size of a sector in SPC traces, block addresses are in sectors
+const SPC_SECTOR_SIZE: u64 = 512;
+
+/// Largest block request in bytes, larger records are ignored instead of
+/// being split into more pages than a trace could hold
+const MAX_RECORD_SIZE: u64 = 1 << 30;
+
+/// Bits of a block trace page number below the device. Blocks on different
+/// devices are different pages, while neighbouring blocks of a device stay
+/// neighbouring pages for the prefetchers
+const DEVICE_SHIFT: u32 = 40;
+
+/// Layout of the trace lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Layout {
+  /// A page optionally followed by `r` or `w`, or a pin directive
+  Plain,
+  /// SPC / UMass: `ASU,LBA,Size,Opcode,Timestamp`, LBA in sectors
+  Spc,
+  /// MSR Cambridge: `Timestamp,Hostname,DiskNumber,Type,Offset,Size,ResponseTime`
+  Msr,
+  /// Twitter cache traces: `timestamp,key,key size,value size,client id,operation,TTL`
+  Twitter,
+}
+
+/// A request read from a block or key-value trace, before it is split
+/// into pages
+struct Record<'a> {
+  /// Operation name, `read` or `write` for block traces
+  op: String,
+  write: bool,
+  target: Target<'a>,
+}
+
+/// What a record accesses
+enum Target<'a> {
+  /// Byte range of a block device, `device` names the device such as the
+  /// ASU of SPC traces
+  Bytes { device: String, offset: u64, size: u64 },
+  /// A single key
+  Key(&'a str),
+}
+
+/// Format of the page requests given with `--input` or stdin. Block traces
+/// are split into one page request per block they touch, keys of key-value
+/// traces are the page identifiers
+#[derive(Clone, Debug)]
+pub struct TraceFormat {
+  layout: Layout,
+  /// Size of a page in bytes for block traces
+  block_size: u64,
+  /// Operations to keep, either names from the trace (`get`) or `read` and
+  /// `write`. Every operation is kept if not set
+  ops: Option<Vec<String>>,
+  /// Number of block requests skipped for being larger than
+  /// `MAX_RECORD_SIZE`
+  oversized: Cell<u64>,
+}
+
+impl TraceFormat {
+  pub fn new(name: &str, block_size: u64, ops: Option<Vec<String>>) -> Self {
+    let layout = match name {
+      "plain" => Layout::Plain,
+      "spc" => Layout::Spc,
+      "msr" => Layout::Msr,
+      "twitter" => Layout::Twitter,
+      _ => unreachable!(),
+    };
+
+    TraceFormat {
+      layout,
+      block_size,
+      ops: ops.map(|x| x.iter().map(|x| x.to_lowercase()).collect()),
+      oversized: Cell::new(0),
+    }
+  }
+
+  /// Warns about block requests skipped while parsing, called once the
+  /// whole trace was read
+  pub fn finish(&self) {
+    if self.oversized.get() > 0 {
+      warn!("Skipped {} block requests larger than {} bytes", self.oversized.get(),
+        MAX_RECORD_SIZE);
+    }
+  }
+
+  /// Checks if an operation passes the filter
+  fn keeps(&self, op: &str, write: bool) -> bool {
+    match self.ops {
+      Some(ref ops) => ops.iter().any(|x| x == op || x == if write { "write" } else { "read" }),
+      None => true,
+    }
+  }
+
+  /// Parses a trace line, adding its page requests or pin directive to the
+  /// entries. Invalid lines, headers and filtered operations are ignored
+  pub fn parse_line<P: PageId>(&self, line: &str, entries: &mut Vec<TraceEntry<P>>) {
+    let record = match self.layout {
+      Layout::Plain => {
+        match util::parse_trace_line(line) {
+          Some(TraceEntry::Request(request)) => {
+            let op = if request.write { "write" } else { "read" };
+            if self.keeps(op, request.write) {
+              entries.push(TraceEntry::Request(request));
+            }
+          },
+          Some(entry) => entries.push(entry),
+          None => {},
+        }
+        return;
+      },
+      Layout::Spc => parse_spc(line),
+      Layout::Msr => parse_msr(line),
+      Layout::Twitter => parse_twitter(line),
+    };
+
+    let record = match record {
+      Some(record) if self.keeps(&record.op, record.write) => record,
+      _ => return,
+    };
+
+    let write = record.write;
+    match record.target {
+      Target::Bytes { device, offset, size } => {
+        if size > MAX_RECORD_SIZE {
+          self.oversized.set(self.oversized.get() + 1);
+          return;
+        }
+
+        // numbered devices keep their number, others are hashed
+        let device = device.parse().unwrap_or_else(|_| hash(&device))
+          & ((1 << (64 - DEVICE_SHIFT)) - 1);
+
+        // every block touched by the byte range, at least one
+        let first = offset / self.block_size;
+        let last = offset.saturating_add(size.max(1) - 1) / self.block_size;
+        for block in first..=last {
+          let number = (device << DEVICE_SHIFT) ^ block;
+          // only string identifiers need the page number as text
+          if let Some(page) = P::from_number(number).or_else(|| page_id(&number.to_string())) {
+            entries.push(TraceEntry::Request(PageRequest { page, write }));
+          }
+        }
+      },
+      Target::Key(key) => {
+        if let Some(page) = page_id(key) {
+          entries.push(TraceEntry::Request(PageRequest { page, write }));
+        }
+      },
+    }
+  }
+}
+
+/// Gets the page identifier of a block number or key, keys that are not
+/// valid identifiers of the page type are hashed to a number
+fn page_id<P: PageId>(key: &str) -> Option<P> {
+  if let Ok(page) = key.parse() {
+    return Some(page);
+  }
+
+  hash(key).to_string().parse().ok()
+}
+
+/// Hashes a key or device name to a number
+fn hash(key: &str) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  key.hash(&mut hasher);
+  hasher.finish()
+}
+
+/// Parses an SPC line: `ASU,LBA,Size,Opcode,Timestamp`
+fn parse_spc<'a>(line: &'a str) -> Option<Record<'a>> {
+  let fields: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+  if fields.len() < 4 {
+    return None;
+  }
+
+  let lba: u64 = fields[1].parse().ok()?;
+  let size = fields[2].parse().ok()?;
+  let write = match fields[3] {
+    "r" | "R" => false,
+    "w" | "W" => true,
+    _ => return None,
+  };
+
+  Some(Record {
+    op: if write { "write" } else { "read" }.to_string(),
+    write,
+    target: Target::Bytes {
+      device: fields[0].to_string(),
+      offset: lba.checked_mul(SPC_SECTOR_SIZE)?,
+      size,
+    },
+  })
+}
+
+/// Parses an MSR Cambridge line:
+/// `Timestamp,Hostname,DiskNumber,Type,Offset,Size,ResponseTime`
+fn parse_msr<'a>(line: &'a str) -> Option<Record<'a>> {
+  let fields: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+  if fields.len() < 6 {
+    return None;
+  }
+
+  let op = fields[3].to_lowercase();
+  let write = match op.as_str() {
+    "read" => false,
+    "write" => true,
+    _ => return None,
+  };
+
+  Some(Record {
+    op,
+    write,
+    target: Target::Bytes {
+      device: format!("{},{}", fields[1], fields[2]),
+      offset: fields[4].parse().ok()?,
+      size: fields[5].parse().ok()?,
+    },
+  })
+}
+
+/// Parses a Twitter cache trace line:
+/// `timestamp,key,key size,value size,client id,operation,TTL`. Gets are
+/// reads and every other operation writes the key, deletes are not
+/// requests since they don't load the key
+fn parse_twitter<'a>(line: &'a str) -> Option<Record<'a>> {
+  let fields: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+  if fields.len() < 6 || fields[1].is_empty() {
+    return None;
+  }
+
+  let op = fields[5].to_lowercase();
+  let write = match op.as_str() {
+    "get" | "gets" => false,
+    "set" | "add" | "replace" | "cas" | "append" | "prepend" | "incr" | "decr" => true,
+    _ => return None,
+  };
+
+  Some(Record {
+    op,
+    write,
+    target: Target::Key(fields[1]),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Pages requested by the lines of a trace
+  fn pages<P: PageId>(format: &TraceFormat, lines: &[&str]) -> Vec<P> {
+    let mut entries = Vec::new();
+    for line in lines {
+      format.parse_line(line, &mut entries);
+    }
+
+    entries
+      .into_iter()
+      .filter_map(|x| match x {
+        TraceEntry::Request(request) => Some(request.page),
+        _ => None,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn block_records_split_into_pages() {
+    let spc = TraceFormat::new("spc", 4096, None);
+    // sectors 7 to 16 touch the first 3 pages
+    assert_eq!(pages::<u64>(&spc, &["0,7,5000,r,0.1"]), vec![0, 1, 2]);
+    assert_eq!(pages::<String>(&spc, &["0,8,1,w,0.1"]), vec!["1".to_string()]);
+
+    // oversized records are ignored instead of expanding forever
+    assert!(pages::<u64>(&spc, &["0,10,18446744073709551615,r,0"]).is_empty());
+    let msr = TraceFormat::new("msr", 4096, None);
+    assert!(pages::<u64>(&msr, &["1,hm,0,Read,0,2147483648,10"]).is_empty());
+    assert_eq!((spc.oversized.get(), msr.oversized.get()), (1, 1));
+  }
+
+  #[test]
+  fn blocks_on_different_devices_are_different_pages() {
+    let spc = TraceFormat::new("spc", 4096, None);
+    let spc_pages = pages::<u64>(&spc, &["0,8,512,r,0.1", "1,8,512,r,0.2", "0,16,512,r,0.3"]);
+    assert_eq!(spc_pages, vec![1, (1 << DEVICE_SHIFT) | 1, 2]);
+
+    let msr = TraceFormat::new("msr", 4096, None);
+    let msr_pages = pages::<u64>(&msr, &["1,hm,0,Read,4096,512,10", "2,hm,1,Read,4096,512,10",
+      "3,web,0,Read,4096,512,10", "4,hm,0,Write,8192,512,10"]);
+    assert_ne!(msr_pages[0], msr_pages[1]);
+    assert_ne!(msr_pages[0], msr_pages[2]);
+    assert_eq!(msr_pages[3], msr_pages[0] + 1);
+  }
+}
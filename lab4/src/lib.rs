@@ -0,0 +1,41 @@
+//! Simulates page replacement algorithms and object caches. Simulations
+//! don't write any output themselves, see `model::observer` to follow a
+//! simulation as it runs
+
+#[macro_use]
+extern crate log;         // logging macros
+
+extern crate chrono;      // time for logging
+extern crate crossterm;   // terminal control for interactive mode
+extern crate ctrlc;       // stopping simulations on interrupt
+extern crate csv;         // csv writer for output data
+extern crate fern;        // logging formatter
+extern crate parking_lot; // more efficient synchronization primitives
+extern crate rand;        // seeded rng for randomized algorithms
+extern crate threadpool;  // thread pool for concurrent simulations
+extern crate toml;        // experiment config files
+extern crate indicatif;   // progress reporting
+extern crate serde;       // serialization for json output
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;  // json output
+#[cfg(test)]
+extern crate proptest;    // property based tests
+
+pub mod analyze;
+pub mod cache;
+pub mod checkpoint;
+pub mod diff;
+pub mod error;
+pub mod events;
+pub mod experiment;
+pub mod hierarchy;
+pub mod histogram;
+pub mod import;
+pub mod model;
+pub mod mrc;
+pub mod progress;
+pub mod simulate;
+pub mod util;
+pub mod visualize;
+
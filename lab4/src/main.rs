@@ -2,24 +2,29 @@
 extern crate clap;        // command line argument parser
 #[macro_use]
 extern crate log;         // logging macros
+extern crate page_replacements;
 
-extern crate chrono;      // time for logging
-extern crate csv;         // csv writer for output data
-extern crate fern;        // logging formatter
-extern crate parking_lot; // more efficient synchronization primitives
-extern crate threadpool;  // thread pool for concurrent simulations
-extern crate indicatif;   // progress reporting
-
-pub mod error;
-pub mod model;
-pub mod simulate;
-pub mod util;
-
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use page_replacements::analyze::{analyze, AnalyzeOptions};
+use page_replacements::cache::{cache, CacheOptions};
+use page_replacements::diff::{diff, DiffOptions};
+use page_replacements::experiment::{run_experiment, ExperimentOptions};
+use page_replacements::hierarchy::{hierarchy, HierarchyRunOptions};
+use page_replacements::histogram::{histogram, HistogramOptions};
+use page_replacements::import::TraceFormat;
+use page_replacements::model::algorithms::{self, AlgorithmParams};
+use page_replacements::model::hierarchy::{HierarchyOptions, LevelOptions};
+use page_replacements::model::prefetch::Prefetcher;
+use page_replacements::model::report::ReportThresholds;
+use page_replacements::util;
+use std::fs;
+use std::io;
 use std::process;
 
-use model::simulation::*;
-use simulate::simulate;
+use page_replacements::model::simulation::*;
+use page_replacements::mrc::{mrc, MrcOptions, Sampling};
+use page_replacements::simulate::{self, simulate};
+use page_replacements::visualize::{visualize, VisualizeOptions};
 
 fn main() {
   // parse args
@@ -27,9 +32,10 @@ fn main() {
     .version(crate_version!())
     .author(crate_authors!())
     .about("Simulates various page replacement algorithms")
+    .setting(AppSettings::SubcommandsNegateReqs)
     .arg(Arg::with_name("table_size")
       .help("Sets the page table size")
-      .required(true)
+      .required_unless("sizes")
       .index(1)
       .validator(util::validate_table_size)
     )
@@ -38,18 +44,50 @@ fn main() {
       .multiple(true)
       .help("Sets the level of verbosity")
     )
+    .arg(Arg::with_name("log_file")
+      .long("log-file")
+      .help("Sets the file to write logs to")
+      .takes_value(true)
+      .default_value("output.log")
+    )
+    .arg(Arg::with_name("no_log_file")
+      .long("no-log-file")
+      .help("Disables writing logs to a file, only logs to stderr")
+    )
+    .arg(Arg::with_name("log_format")
+      .long("log-format")
+      .help("Sets the format of log messages")
+      .takes_value(true)
+      .default_value("text")
+      .possible_values(&["text", "json"])
+    )
+    .arg(Arg::with_name("log_level")
+      .long("log-level")
+      .help("Sets the log level of a module, ex: page_replacements::simulate=warn")
+      .takes_value(true)
+      .multiple(true)
+      .number_of_values(1)
+      .validator(util::validate_level_filter)
+    )
     .arg(Arg::with_name("stdout")
       .short("s")
       .long("stdout")
       .help("Enables stdout logging for each page fault")
     )
+    .arg(Arg::with_name("summary")
+      .long("summary")
+      .help("Prints a table of the results of every simulation when they finish")
+    )
     .arg(Arg::with_name("algorithm")
       .short("a")
       .long("algorithm")
-      .help("Sets the page replacement algorithm to use")
+      .help("Sets the page replacement algorithm(s) to use, separated by commas or repeated")
       .required(true)
       .takes_value(true)
-      .possible_values(&["fifo", "lru", "second_chance", "sc", "optimal"])
+      .multiple(true)
+      .number_of_values(1)
+      .use_delimiter(true)
+      .possible_values(&["fifo", "lru", "second_chance", "sc", "optimal", "random", "nru"])
     )
     .arg(Arg::with_name("to_table_size")
       .short("t")
@@ -58,6 +96,20 @@ fn main() {
       .takes_value(true)
       .validator(util::validate_table_size)
     )
+    .arg(Arg::with_name("step")
+      .long("step")
+      .help("Sets the step between table sizes of a range of sizes")
+      .takes_value(true)
+      .requires("to_table_size")
+      .validator(util::validate_table_size)
+    )
+    .arg(Arg::with_name("sizes")
+      .long("sizes")
+      .help("Sets a list of table sizes to test, ex: 4,8,16,64 or 10..500:10 or log:10..10000:40")
+      .takes_value(true)
+      .conflicts_with_all(&["table_size", "to_table_size"])
+      .validator(util::validate_table_sizes)
+    )
     .arg(Arg::with_name("input")
       .short("i")
       .long("input")
@@ -70,66 +122,895 @@ fn main() {
       .help("Sets the output csv file to write results to")
       .takes_value(true)
     )
+    .arg(Arg::with_name("events")
+      .short("e")
+      .long("events")
+      .help("Sets the file to write the result of each page request to")
+      .takes_value(true)
+    )
+    .arg(Arg::with_name("events_format")
+      .long("events-format")
+      .help("Sets the format of the events file")
+      .takes_value(true)
+      .default_value("jsonl")
+      .possible_values(&["jsonl", "csv"])
+    )
+    .arg(Arg::with_name("page_report")
+      .long("page-report")
+      .help("Sets the csv file to write the accesses, faults, evictions and mean residency \
+        of each page to")
+      .takes_value(true)
+    )
+    .arg(Arg::with_name("hot_ratio")
+      .long("hot-ratio")
+      .help("Sets the ratio of the mean requests per page a page needs to be hot")
+      .takes_value(true)
+      .default_value("2")
+      .validator(util::validate_ratio)
+    )
+    .arg(Arg::with_name("cold_ratio")
+      .long("cold-ratio")
+      .help("Sets the ratio of the mean requests per page a page can have to be cold")
+      .takes_value(true)
+      .default_value("0.5")
+      .validator(util::validate_ratio)
+    )
+    .arg(Arg::with_name("thrash_distance")
+      .long("thrash-distance")
+      .help("Sets the max number of requests between evicting a page and faulting it again \
+        to count as a refault")
+      .takes_value(true)
+      .default_value("100")
+      .validator(util::validate_number)
+    )
+    .arg(Arg::with_name("thrash_refaults")
+      .long("thrash-refaults")
+      .help("Sets the number of refaults for a page to be thrashing")
+      .takes_value(true)
+      .default_value("3")
+      .validator(util::validate_positive)
+    )
+    .arg(Arg::with_name("jobs")
+      .short("j")
+      .long("jobs")
+      .help("Sets the number of threads for concurrent simulations, defaults to the number of CPUs")
+      .takes_value(true)
+      .validator(util::validate_positive)
+    )
+    .arg(Arg::with_name("memory_ns")
+      .long("memory-ns")
+      .help("Sets the memory access time in nanoseconds")
+      .takes_value(true)
+      .default_value("100")
+      .validator(util::validate_latency)
+    )
+    .arg(Arg::with_name("fault_ns")
+      .long("fault-ns")
+      .help("Sets the time to load a page on a page fault in nanoseconds")
+      .takes_value(true)
+      .default_value("8000000")
+      .validator(util::validate_latency)
+    )
+    .arg(Arg::with_name("write_back_ns")
+      .long("write-back-ns")
+      .help("Sets the time to write back an evicted dirty page in nanoseconds")
+      .takes_value(true)
+      .default_value("8000000")
+      .validator(util::validate_latency)
+    )
+    .arg(Arg::with_name("prefetch")
+      .long("prefetch")
+      .help("Sets the prefetcher to load pages ahead of page faults")
+      .takes_value(true)
+      .possible_values(&["sequential", "stride", "cluster"])
+    )
+    .arg(Arg::with_name("prefetch_degree")
+      .long("prefetch-degree")
+      .help("Sets the number of pages loaded by the prefetcher, or the cluster size")
+      .takes_value(true)
+      .default_value("4")
+      .validator(util::validate_positive)
+    )
+    .arg(Arg::with_name("seed")
+      .long("seed")
+      .help("Sets the seed of the random and nru algorithms, the first seed of repeated runs")
+      .takes_value(true)
+      .default_value("0")
+      .validator(util::validate_number)
+    )
+    .arg(Arg::with_name("repeat")
+      .long("repeat")
+      .help("Sets the number of seeds to run random and nru with, reports the mean and \
+        standard deviation of the hit rate")
+      .takes_value(true)
+      .default_value("1")
+      .validator(util::validate_positive)
+    )
+    .arg(Arg::with_name("nru_interval")
+      .long("nru-interval")
+      .help("Sets the number of page requests between clearing the referenced bits of nru, \
+        0 never clears them")
+      .takes_value(true)
+      .default_value("100")
+      .validator(util::validate_number)
+    )
+    .arg(Arg::with_name("pins")
+      .long("pins")
+      .help("Sets a file of pages to pin and unpin, ex: pin 5072 or 100 unpin 5072")
+      .takes_value(true)
+    )
+    .arg(Arg::with_name("trace_format")
+      .long("trace-format")
+      .help("Sets the format of the trace, spc and msr are block traces split into pages, \
+        twitter uses the keys of a key-value trace as pages")
+      .takes_value(true)
+      .default_value("plain")
+      .possible_values(&["plain", "spc", "msr", "twitter"])
+    )
+    .arg(Arg::with_name("block_size")
+      .long("block-size")
+      .help("Sets the page size in bytes to split block traces into, ex: 4096 or 4K")
+      .takes_value(true)
+      .default_value("4K")
+      .validator(util::validate_capacity)
+    )
+    .arg(Arg::with_name("ops")
+      .long("ops")
+      .help("Sets the operations of the trace to keep, ex: read or get,gets. \
+        Keeps every operation if not given")
+      .takes_value(true)
+      .multiple(true)
+      .number_of_values(1)
+      .use_delimiter(true)
+    )
+    .arg(Arg::with_name("page_ids")
+      .long("page-ids")
+      .help("Sets the type of page identifiers in the trace, string allows any identifier \
+        without whitespace or commas")
+      .takes_value(true)
+      .default_value("u64")
+      .possible_values(&["u64", "u128", "string"])
+    )
+    .arg(Arg::with_name("checkpoint")
+      .long("checkpoint")
+      .help("Sets the file to save completed simulations to, defaults to the output file \
+        with .checkpoint, which is removed once the results are saved. Requires an \
+        input file")
+      .takes_value(true)
+      .requires("input")
+    )
+    .arg(Arg::with_name("resume")
+      .long("resume")
+      .help("Skips simulations already saved in the checkpoint file")
+      .requires("input")
+    )
+    .arg(Arg::with_name("progress")
+      .long("progress")
+      .help("Sets how progress of a range of simulations is shown, auto only shows \
+        the bar if stderr is a terminal")
+      .takes_value(true)
+      .default_value("auto")
+      .possible_values(&["auto", "bar", "json", "none"])
+    )
+    .subcommand(SubCommand::with_name("analyze")
+      .about("Reports statistics of a page request trace")
+      .arg(Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .help("Input file for page file access numbers, reads stdin if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .help("Sets the output file to write statistics to, prints to stdout if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .help("Sets the output format")
+        .takes_value(true)
+        .default_value("text")
+        .possible_values(&["text", "json", "csv"])
+      )
+      .arg(Arg::with_name("top")
+        .short("n")
+        .long("top")
+        .help("Sets the number of most requested pages to show")
+        .takes_value(true)
+        .default_value("10")
+        .validator(util::validate_positive)
+      )
+    )
+    .subcommand(SubCommand::with_name("cache")
+      .about("Simulates object caches with a capacity in bytes on requests of a key and size")
+      .arg(Arg::with_name("capacity")
+        .help("Sets the cache capacities in bytes separated by commas, ex: 64M or 512K,1M,2G")
+        .required(true)
+        .index(1)
+        .multiple(true)
+        .use_delimiter(true)
+        .validator(util::validate_capacity)
+      )
+      .arg(Arg::with_name("algorithm")
+        .short("a")
+        .long("algorithm")
+        .help("Sets the cache replacement algorithm(s) to use, separated by commas or repeated")
+        .required(true)
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .use_delimiter(true)
+        .possible_values(&["lru", "fifo", "size_lru", "gdsf"])
+      )
+      .arg(Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .help("Input file of object requests, a key and size in bytes per line, reads stdin if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .help("Sets the output file to write results to, prints to stdout if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .help("Sets the output format")
+        .takes_value(true)
+        .default_value("text")
+        .possible_values(&["text", "json", "csv"])
+      )
+    )
+    .subcommand(SubCommand::with_name("diff")
+      .about("Compares the results of two algorithms for each page request")
+      .arg(Arg::with_name("table_size")
+        .help("Sets the page table size")
+        .required(true)
+        .index(1)
+        .validator(util::validate_table_size)
+      )
+      .arg(Arg::with_name("algorithm")
+        .short("a")
+        .long("algorithm")
+        .help("Sets the first page replacement algorithm to compare")
+        .required(true)
+        .takes_value(true)
+        .possible_values(&["fifo", "lru", "second_chance", "sc", "optimal", "random", "nru"])
+      )
+      .arg(Arg::with_name("compare")
+        .short("b")
+        .long("compare")
+        .help("Sets the second page replacement algorithm to compare")
+        .required(true)
+        .takes_value(true)
+        .possible_values(&["fifo", "lru", "second_chance", "sc", "optimal", "random", "nru"])
+      )
+      .arg(Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .help("Input file for page file access numbers, reads stdin if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .help("Sets the output file to write differences to, prints to stdout if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .help("Sets the output format")
+        .takes_value(true)
+        .default_value("text")
+        .possible_values(&["text", "json", "csv"])
+      )
+      .arg(Arg::with_name("limit")
+        .short("n")
+        .long("limit")
+        .help("Sets the max number of requests and pages shown in text output")
+        .takes_value(true)
+        .default_value("20")
+        .validator(util::validate_positive)
+      )
+    )
+    .subcommand(SubCommand::with_name("experiment")
+      .about("Runs experiments declared in a TOML file")
+      .setting(AppSettings::SubcommandRequiredElseHelp)
+      .subcommand(SubCommand::with_name("run")
+        .about("Runs every combination of traces, algorithms, sizes and parameters of an experiment")
+        .arg(Arg::with_name("config")
+          .help("Sets the experiment file")
+          .required(true)
+          .index(1)
+        )
+        .arg(Arg::with_name("jobs")
+          .short("j")
+          .long("jobs")
+          .help("Sets the number of threads for concurrent simulations, defaults to the number of CPUs")
+          .takes_value(true)
+          .validator(util::validate_positive)
+        )
+        .arg(Arg::with_name("progress")
+          .long("progress")
+          .help("Sets how progress of the simulations is shown, auto only shows the bar if stderr \
+            is a terminal")
+          .takes_value(true)
+          .default_value("auto")
+          .possible_values(&["auto", "bar", "json", "none"])
+        )
+      )
+    )
+    .subcommand(SubCommand::with_name("hierarchy")
+      .about("Simulates a TLB in front of the page table, optionally backed by a secondary level")
+      .arg(Arg::with_name("table_size")
+        .help("Sets the page table size")
+        .required(true)
+        .index(1)
+        .validator(util::validate_table_size)
+      )
+      .arg(Arg::with_name("algorithm")
+        .short("a")
+        .long("algorithm")
+        .help("Sets the page replacement algorithm of the page table")
+        .required(true)
+        .takes_value(true)
+        .possible_values(&["fifo", "lru", "second_chance", "sc", "optimal", "random", "nru"])
+      )
+      .arg(Arg::with_name("tlb_size")
+        .long("tlb-size")
+        .help("Sets the number of TLB entries, no TLB is used if not given")
+        .takes_value(true)
+        .validator(util::validate_table_size)
+      )
+      .arg(Arg::with_name("tlb_algorithm")
+        .long("tlb-algorithm")
+        .help("Sets the replacement algorithm of the TLB")
+        .takes_value(true)
+        .default_value("lru")
+        .possible_values(&["fifo", "lru", "second_chance", "sc", "random", "nru"])
+      )
+      .arg(Arg::with_name("secondary_size")
+        .long("secondary-size")
+        .help("Sets the number of pages in the secondary level, no secondary level is used if not given")
+        .takes_value(true)
+        .validator(util::validate_table_size)
+      )
+      .arg(Arg::with_name("secondary_algorithm")
+        .long("secondary-algorithm")
+        .help("Sets the replacement algorithm of the secondary level")
+        .takes_value(true)
+        .default_value("lru")
+        .possible_values(&["fifo", "lru", "second_chance", "sc", "random", "nru"])
+      )
+      .arg(Arg::with_name("tlb_ns")
+        .long("tlb-ns")
+        .help("Sets the TLB lookup time in nanoseconds")
+        .takes_value(true)
+        .default_value("20")
+        .validator(util::validate_latency)
+      )
+      .arg(Arg::with_name("memory_ns")
+        .long("memory-ns")
+        .help("Sets the memory access time in nanoseconds, TLB misses add one access")
+        .takes_value(true)
+        .default_value("100")
+        .validator(util::validate_latency)
+      )
+      .arg(Arg::with_name("secondary_ns")
+        .long("secondary-ns")
+        .help("Sets the secondary level access time in nanoseconds")
+        .takes_value(true)
+        .default_value("10000")
+        .validator(util::validate_latency)
+      )
+      .arg(Arg::with_name("fault_ns")
+        .long("fault-ns")
+        .help("Sets the time to load a page from the backing store in nanoseconds")
+        .takes_value(true)
+        .default_value("8000000")
+        .validator(util::validate_latency)
+      )
+      .arg(Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .help("Input file for page file access numbers, reads stdin if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .help("Sets the output file to write results to, prints to stdout if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .help("Sets the output format")
+        .takes_value(true)
+        .default_value("text")
+        .possible_values(&["text", "json", "csv"])
+      )
+    )
+    .subcommand(SubCommand::with_name("histogram")
+      .about("Writes reuse distance and inter-reference gap histograms of a trace as csv")
+      .arg(Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .help("Input file for page file access numbers, reads stdin if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .help("Sets the output csv file to write histograms to, prints to stdout if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("mode")
+        .short("m")
+        .long("mode")
+        .help("Sets the buckets, log uses log2 sized buckets and exact has a bucket for every value")
+        .takes_value(true)
+        .default_value("log")
+        .possible_values(&["log", "exact"])
+      )
+    )
+    .subcommand(SubCommand::with_name("mrc")
+      .about("Estimates LRU miss ratio curves from a sample of the pages (SHARDS)")
+      .arg(Arg::with_name("sizes")
+        .help("Sets the table sizes to estimate, ex: 4,8,16,64 or 10..500:10 or log:10..10000:40")
+        .required(true)
+        .index(1)
+        .validator(util::validate_table_sizes)
+      )
+      .arg(Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .help("Input file for page file access numbers, reads stdin if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .help("Sets the output file to write the miss ratios to, prints to stdout if not given")
+        .takes_value(true)
+      )
+      .arg(Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .help("Sets the output format")
+        .takes_value(true)
+        .default_value("text")
+        .possible_values(&["text", "json", "csv"])
+      )
+      .arg(Arg::with_name("rate")
+        .short("r")
+        .long("rate")
+        .help("Sets the fraction of pages to sample, defaults to 0.01")
+        .takes_value(true)
+        .validator(util::validate_rate)
+      )
+      .arg(Arg::with_name("max_pages")
+        .long("max-pages")
+        .help("Samples at most this many pages, lowering the rate as new pages are requested")
+        .takes_value(true)
+        .conflicts_with("rate")
+        .validator(util::validate_positive)
+      )
+      .arg(Arg::with_name("validate")
+        .long("validate")
+        .help("Compares the estimates to the exact miss ratios, keeps the whole trace in memory")
+      )
+    )
+    .subcommand(SubCommand::with_name("visualize")
+      .about("Steps through page requests interactively, showing the page table")
+      .arg(Arg::with_name("table_size")
+        .help("Sets the page table size")
+        .required(true)
+        .index(1)
+        .validator(util::validate_table_size)
+      )
+      .arg(Arg::with_name("algorithm")
+        .short("a")
+        .long("algorithm")
+        .help("Sets the page replacement algorithm to use")
+        .required(true)
+        .takes_value(true)
+        .possible_values(&["fifo", "lru", "second_chance", "sc", "optimal", "random", "nru"])
+      )
+      .arg(Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .help("Input file for page file access numbers")
+        .required(true)
+        .takes_value(true)
+      )
+    )
     .get_matches();
-  
-  // parse table size
-  let table_size = args
-    .value_of("table_size")
-    .and_then(|x| x.parse::<usize>().ok())
-    .unwrap(); // ok to unwrap here, input already validated in clap
-  
-  let verbosity: u64 = args.occurrences_of("verbose");
-  if let Err(e) = util::setup_logger(verbosity) {
+
+  let log_options = util::LogOptions {
+    verbosity: args.occurrences_of("verbose"),
+    file: if args.is_present("no_log_file") {
+      None
+    } else {
+      args.value_of("log_file")
+    },
+    // safe to unwrap, has default value
+    format: args.value_of("log_format").unwrap(),
+    filters: args.values_of("log_level").map(|x| x.collect()).unwrap_or_default(),
+  };
+
+  if let Err(e) = util::setup_logger(log_options) {
     eprintln!("Error setting up logging: {}", e);
     process::exit(1);
   }
 
+  if let Some(sub_args) = args.subcommand_matches("analyze") {
+    run_analyze(sub_args);
+    return;
+  }
+
+  if let Some(sub_args) = args.subcommand_matches("cache") {
+    run_cache(sub_args);
+    return;
+  }
+
+  if let Some(sub_args) = args.subcommand_matches("diff") {
+    run_diff(sub_args);
+    return;
+  }
+
+  if let Some(sub_args) = args.subcommand_matches("experiment") {
+    run_experiment_command(sub_args);
+    return;
+  }
+
+  if let Some(sub_args) = args.subcommand_matches("hierarchy") {
+    run_hierarchy(sub_args);
+    return;
+  }
+
+  if let Some(sub_args) = args.subcommand_matches("histogram") {
+    run_histogram(sub_args);
+    return;
+  }
+
+  if let Some(sub_args) = args.subcommand_matches("mrc") {
+    run_mrc(sub_args);
+    return;
+  }
+
+  if let Some(sub_args) = args.subcommand_matches("visualize") {
+    run_visualize(sub_args);
+    return;
+  }
+
   let should_stdout = args.is_present("stdout");
 
   // safe to unwrap, required & validated in clap
-  let algorithm = args.value_of("algorithm").unwrap();
+  let algorithms: Vec<&str> = args.values_of("algorithm").unwrap().collect();
+  let algorithm_names = algorithms.join(", ").to_uppercase();
 
   // optional file input
   let input = args.value_of("input");
 
-  let to_table_size = args
-    .value_of("to_table_size")
-    .and_then(|x| x.parse::<usize>().ok());
+  // ok to unwrap parsing here, input already validated in clap
+  let table_sizes = if let Some(sizes) = args.value_of("sizes") {
+    util::parse_table_sizes(sizes).unwrap()
+  } else {
+    // parse table size, required if there is no size list
+    let table_size = args
+      .value_of("table_size")
+      .and_then(|x| x.parse::<usize>().ok())
+      .unwrap();
+
+    let to_table_size = args
+      .value_of("to_table_size")
+      .and_then(|x| x.parse::<usize>().ok())
+      .unwrap_or(table_size);
+
+    let step = args
+      .value_of("step")
+      .and_then(|x| x.parse::<usize>().ok())
+      .unwrap_or(1);
 
-  // check if testing a range of page table sizes
-  if let Some(size_to) = to_table_size {
-    if size_to < table_size {
+    // check if testing a range of page table sizes
+    if to_table_size < table_size {
       error!("Max table size (-t size) cannot be lower than table size");
       process::exit(1);
     }
-    info!("Using page replacement algorithm {} for table sizes {} -> {}",
-      algorithm.to_uppercase(), table_size, size_to);
+
+    (table_size..=to_table_size).step_by(step).collect::<Vec<_>>()
+  };
+
+  if table_sizes.len() > 1 {
+    info!("Using page replacement algorithm {} for {} table sizes {} -> {}",
+      algorithm_names, table_sizes.len(), table_sizes[0], table_sizes[table_sizes.len() - 1]);
   } else {
     info!("Using page replacement algorithm {} for table size {}",
-      algorithm.to_uppercase(), table_size);
+      algorithm_names, table_sizes[0]);
+  }
+
+  // save completed simulations next to the output by default, without a
+  // .csv extension so it is not read as an output file
+  let default_checkpoint = args.value_of("output")
+    .filter(|_| args.is_present("input"))
+    .map(|x| format!("{}.checkpoint", x.trim_end_matches(".csv")));
+  let checkpoint = args.value_of("checkpoint").or(default_checkpoint.as_deref());
+
+  let resume = args.is_present("resume");
+  if resume && checkpoint.is_none() {
+    error!("Resuming requires a checkpoint file (--checkpoint file) or output file (-o file)");
+    process::exit(1);
+  }
+
+  // optimal looks ahead in the trace per page request, loading other pages
+  // would move it out of sync with the trace
+  if args.is_present("prefetch") && algorithms.contains(&"optimal") {
+    error!("Prefetching cannot be used with the optimal algorithm");
+    process::exit(1);
+  }
+
+  // prefetchers pick pages by number
+  if args.is_present("prefetch") && args.value_of("page_ids") == Some("string") {
+    error!("Prefetching cannot be used with string page identifiers");
+    process::exit(1);
   }
 
+  // repeated runs of the same table size would overwrite each other's events
+  let repeat: usize = args.value_of("repeat").unwrap().parse().unwrap();
+  let repeated = repeat > 1 && algorithms.iter().any(|&x| algorithms::is_randomized(x));
+  if args.is_present("events") && repeated {
+    error!("Events cannot be written for repeated simulations (--repeat)");
+    process::exit(1);
+  }
+
+  if args.is_present("page_report") && repeated {
+    error!("Page reports cannot be written for repeated simulations (--repeat)");
+    process::exit(1);
+  }
+
+  let prefetcher = args.value_of("prefetch").map(|kind| Prefetcher::new(kind,
+    args.value_of("prefetch_degree").unwrap().parse().unwrap()));
+
   let options = SimulationOptions {
     input,
-    table_size,
-    to_table_size,
-    algorithm,
+    table_sizes: table_sizes.clone(),
+    algorithms,
     should_stdout,
+    events: args.value_of("events"),
+    // safe to unwrap, has default values
+    events_format: args.value_of("events_format").unwrap(),
+    page_report: args.value_of("page_report"),
+    report_thresholds: ReportThresholds {
+      hot_ratio: args.value_of("hot_ratio").unwrap().parse().unwrap(),
+      cold_ratio: args.value_of("cold_ratio").unwrap().parse().unwrap(),
+      thrash_distance: args.value_of("thrash_distance").unwrap().parse().unwrap(),
+      thrash_refaults: args.value_of("thrash_refaults").unwrap().parse().unwrap(),
+    },
+    progress: args.value_of("progress").unwrap(),
+    jobs: args.value_of("jobs").and_then(|x| x.parse().ok()),
+    checkpoint,
+    resume,
+    cost: CostModel {
+      memory_ns: args.value_of("memory_ns").unwrap().parse().unwrap(),
+      fault_ns: args.value_of("fault_ns").unwrap().parse().unwrap(),
+      write_back_ns: args.value_of("write_back_ns").unwrap().parse().unwrap(),
+    },
+    prefetcher,
+    format: TraceFormat::new(args.value_of("trace_format").unwrap(),
+      util::parse_bytes(args.value_of("block_size").unwrap()).unwrap(),
+      args.values_of("ops").map(|x| x.map(|x| x.to_string()).collect())),
+    pins: args.value_of("pins"),
+    // safe to unwrap, has default value
+    page_ids: args.value_of("page_ids").unwrap(),
+    params: AlgorithmParams {
+      seed: args.value_of("seed").unwrap().parse().unwrap(),
+      nru_interval: args.value_of("nru_interval").unwrap().parse().unwrap(),
+    },
+    repeat,
   };
   
   // run simulation(s)
-  let hit_rates = match simulate(options) {
-    Ok(rates) => rates,
+  let results = match simulate(options) {
+    Ok(results) => results,
     Err(e) => {
       error!("Failed simulation: {}", e);
       process::exit(1);
     }
   };
 
-  // save hit rates to csv file
+  if args.is_present("summary") {
+    if let Err(e) = util::write_summary(&mut io::stdout(), &results) {
+      error!("Failed to write summary: {}", e);
+    }
+  }
+
+  let complete = simulate::is_complete(&results, &table_sizes, repeat);
+
+  // save hit rates to a csv file for each algorithm
+  let mut saved = true;
   if let Some(output_file) = args.value_of("output") {
-    if let Err(e) = util::save_result(output_file, algorithm, hit_rates) {
-      error!("Failed to save results: {}", e);
+    for (algorithm, algorithm_results) in results {
+      if let Err(e) = util::save_result(output_file, &algorithm, algorithm_results) {
+        error!("Failed to save results: {}", e);
+        saved = false;
+      }
     }
   }
+
+  // the default checkpoint is only kept to resume interrupted simulations
+  if let Some(path) = default_checkpoint {
+    if complete && saved && args.value_of("checkpoint").is_none() {
+      match fs::remove_file(&path) {
+        Ok(()) => debug!("Removed checkpoint {}", path),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => warn!("Failed to remove checkpoint {}: {}", path, e),
+      }
+    }
+  }
+}
+
+/// Runs the analyze subcommand
+fn run_analyze(args: &ArgMatches) {
+  let options = AnalyzeOptions {
+    input: args.value_of("input"),
+    output: args.value_of("output"),
+    // safe to unwrap, has default values & validated in clap
+    format: args.value_of("format").unwrap(),
+    top: args.value_of("top").unwrap().parse().unwrap(),
+  };
+
+  if let Err(e) = analyze(options) {
+    error!("Failed trace analysis: {}", e);
+    process::exit(1);
+  }
+}
+
+/// Runs the cache subcommand
+fn run_cache(args: &ArgMatches) {
+  // safe to unwrap, required or has default values & validated in clap
+  let options = CacheOptions {
+    input: args.value_of("input"),
+    output: args.value_of("output"),
+    format: args.value_of("format").unwrap(),
+    capacities: args.values_of("capacity").unwrap()
+      .map(|x| util::parse_bytes(x).unwrap())
+      .collect(),
+    algorithms: args.values_of("algorithm").unwrap().collect(),
+  };
+
+  if let Err(e) = cache(options) {
+    error!("Failed cache simulation: {}", e);
+    process::exit(1);
+  }
+}
+
+/// Runs the diff subcommand
+fn run_diff(args: &ArgMatches) {
+  // safe to unwrap, required or has default values & validated in clap
+  let options = DiffOptions {
+    input: args.value_of("input"),
+    output: args.value_of("output"),
+    format: args.value_of("format").unwrap(),
+    table_size: args.value_of("table_size").unwrap().parse().unwrap(),
+    algorithm_a: args.value_of("algorithm").unwrap(),
+    algorithm_b: args.value_of("compare").unwrap(),
+    limit: args.value_of("limit").unwrap().parse().unwrap(),
+  };
+
+  if let Err(e) = diff(options) {
+    error!("Failed comparison: {}", e);
+    process::exit(1);
+  }
+}
+
+/// Runs the experiment subcommand
+fn run_experiment_command(args: &ArgMatches) {
+  // run is the only experiment subcommand, required by clap
+  let args = args.subcommand_matches("run").unwrap();
+  let options = ExperimentOptions {
+    // safe to unwrap, required or has default values & validated in clap
+    config: args.value_of("config").unwrap(),
+    jobs: args.value_of("jobs").and_then(|x| x.parse().ok()),
+    progress: args.value_of("progress").unwrap(),
+  };
+
+  if let Err(e) = run_experiment(options) {
+    error!("Failed experiment: {}", e);
+    process::exit(1);
+  }
+}
+
+/// Runs the hierarchy subcommand
+fn run_hierarchy(args: &ArgMatches) {
+  // safe to unwrap, required or has default values & validated in clap
+  let latency = |name| args.value_of(name).unwrap().parse().unwrap();
+  let size = |name| args.value_of(name).map(|x| x.parse().unwrap());
+
+  let options = HierarchyRunOptions {
+    input: args.value_of("input"),
+    output: args.value_of("output"),
+    format: args.value_of("format").unwrap(),
+    hierarchy: HierarchyOptions {
+      tlb: size("tlb_size").map(|size| LevelOptions {
+        size,
+        algorithm: args.value_of("tlb_algorithm").unwrap(),
+        latency_ns: latency("tlb_ns"),
+      }),
+      memory: LevelOptions {
+        size: size("table_size").unwrap(),
+        algorithm: args.value_of("algorithm").unwrap(),
+        latency_ns: latency("memory_ns"),
+      },
+      secondary: size("secondary_size").map(|size| LevelOptions {
+        size,
+        algorithm: args.value_of("secondary_algorithm").unwrap(),
+        latency_ns: latency("secondary_ns"),
+      }),
+      fault_ns: latency("fault_ns"),
+    },
+  };
+
+  if let Err(e) = hierarchy(options) {
+    error!("Failed hierarchy simulation: {}", e);
+    process::exit(1);
+  }
+}
+
+/// Runs the histogram subcommand
+fn run_histogram(args: &ArgMatches) {
+  let options = HistogramOptions {
+    input: args.value_of("input"),
+    output: args.value_of("output"),
+    // safe to unwrap, has default value
+    mode: args.value_of("mode").unwrap(),
+  };
+
+  if let Err(e) = histogram(options) {
+    error!("Failed to write histograms: {}", e);
+    process::exit(1);
+  }
+}
+
+/// Runs the mrc subcommand
+fn run_mrc(args: &ArgMatches) {
+  // safe to unwrap, required or has default values & validated in clap
+  let sampling = match args.value_of("max_pages") {
+    Some(max_pages) => Sampling::FixedSize(max_pages.parse().unwrap()),
+    None => Sampling::Rate(args.value_of("rate").map_or(0.01, |x| x.parse().unwrap())),
+  };
+
+  let options = MrcOptions {
+    input: args.value_of("input"),
+    output: args.value_of("output"),
+    format: args.value_of("format").unwrap(),
+    table_sizes: util::parse_table_sizes(args.value_of("sizes").unwrap()).unwrap(),
+    sampling,
+    validate: args.is_present("validate"),
+  };
+
+  if let Err(e) = mrc(options) {
+    error!("Failed to estimate miss ratio curve: {}", e);
+    process::exit(1);
+  }
+}
+
+/// Runs the visualize subcommand
+fn run_visualize(args: &ArgMatches) {
+  // safe to unwrap, required & validated in clap
+  let options = VisualizeOptions {
+    input: args.value_of("input").unwrap(),
+    table_size: args.value_of("table_size").unwrap().parse().unwrap(),
+    algorithm: args.value_of("algorithm").unwrap(),
+  };
+
+  if let Err(e) = visualize(options) {
+    error!("Failed visualization: {}", e);
+    process::exit(1);
+  }
 }
@@ -1,46 +1,89 @@
+use super::{Frame, Outcome, TableState};
+use error::{Error, Result};
+use model::page::PageId;
+use std::collections::HashSet;
+
 /// A page table entry for FIFO page replacement
-#[derive (Debug)]
-pub struct Fifo {
-  /// Vec of page numbers
-  table: Vec<u64>,
+#[derive (Clone, Debug)]
+pub struct Fifo<P = u64> {
+  /// Vec of page numbers, None if the frame is empty
+  table: Vec<Option<P>>,
   /// Size of page table
   size: usize,
   /// Index position
   index: usize,
 }
 
-impl Fifo {
+impl<P: PageId> Fifo<P> {
   pub fn new(size: usize) -> Self {
     Fifo {
-      table: vec![0; size], // initialize vec with size 0s
-      size: size,
+      table: vec![None; size], // initialize vec with size empty frames
+      size,
       index: 0,
     }
   }
 
-  /// Handles a page request, returns true if page fault occurred
-  pub fn handle_page_request(&mut self, page_request: u64, should_stdout: bool) -> bool {
-    if !self.table.contains(&page_request) {
-      if should_stdout {
-        println!("Page {} caused a page fault", page_request);
+  /// Handles a page request, returns if a page fault occurred and which
+  /// page was replaced. Pinned pages are skipped, errors if every frame
+  /// is pinned
+  pub fn handle_page_request(&mut self, page_request: P, pinned: &HashSet<P>)
+    -> Result<Outcome<P>> {
+    if !self.contains(&page_request) {
+      // move the hand past pinned pages, empty frames are never pinned
+      let mut skipped = 0;
+      while !pinned.is_empty()
+        && self.table[self.index].as_ref().is_some_and(|x| pinned.contains(x)) {
+        skipped += 1;
+        if skipped == self.size {
+          return Err(Error::Paging(format!(
+            "All frames are pinned, cannot load page {}", page_request)));
+        }
+
+        self.index = (self.index + 1) % self.size;
       }
 
       // safe to unwrap, self.index should never go >= len 
-      {
+      let outcome = {
         let elem = self.table.get_mut(self.index).unwrap();
-        trace!("SWAP: {} -> {} at index {}", *elem, page_request, self.index);
-        *elem = page_request;
-      }
+        Outcome::Fault {
+          evicted: elem.replace(page_request),
+          frame: self.index,
+        }
+      };
 
       self.index = (self.index + 1) % self.size;
 
-      debug!("{:?}", self.table);
-      return true;
+      return Ok(outcome);
     }
 
     // we don't move existing page to beginning
     // since that would be basically just lru?
-    debug!("{:?}", self.table);
-    false
+    Ok(Outcome::Hit)
+  }
+
+  /// Checks if a page is in the table without counting it as a request
+  pub fn contains(&self, page: &P) -> bool {
+    self.table.iter().any(|x| x.as_ref() == Some(page))
+  }
+
+  /// Removes a page from the table, the frame is filled again once the
+  /// hand reaches it. Returns if the page was in the table
+  pub fn invalidate(&mut self, page: &P) -> bool {
+    match self.table.iter().position(|x| x.as_ref() == Some(page)) {
+      Some(index) => {
+        self.table[index] = None;
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Gets a snapshot of the page table, the hand points to the next page
+  /// to be replaced
+  pub fn state(&self) -> TableState<P> {
+    TableState {
+      frames: self.table.iter().map(|page| Frame::new(page.clone())).collect(),
+      hand: Some(self.index),
+    }
   }
 }
@@ -1,62 +1,72 @@
+use super::{Frame, Outcome, TableState};
+use error::{Error, Result};
+use model::page::PageId;
+use std::collections::HashSet;
 use std::fmt;
+use std::mem;
 
 /// A page table entry for LRU page replacement
 #[derive (Clone)]
-pub struct LruPage {
-  /// Page number
-  number: u64,
+pub struct LruPage<P> {
+  /// Page number, None if the frame is empty
+  number: Option<P>,
   /// "Time" of insertion
   time: u64,
 }
 
-impl LruPage {
-  /// Creates a new LRU page
+impl<P> LruPage<P> {
+  /// Creates an empty LRU page
   fn new() -> Self {
     LruPage {
-      number: 0,
+      number: None,
       time: 0,
     }
   }
 }
 
-impl fmt::Debug for LruPage {
+impl<P: fmt::Debug> fmt::Debug for LruPage<P> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "{} ({})", self.number, self.time)
+    match self.number {
+      Some(ref number) => write!(f, "{:?} ({})", number, self.time),
+      None => write!(f, "- ({})", self.time),
+    }
   }
 }
 
 /// A page table for LRU page replacement
-#[derive (Debug)]
-pub struct Lru {
+#[derive (Clone, Debug)]
+pub struct Lru<P = u64> {
   /// Vec of page numbers
-  table: Vec<LruPage>,
-  /// Size of page table
-  size: usize,
+  table: Vec<LruPage<P>>,
   /// Current "time"
   time: u64,
 }
 
-impl Lru {
+impl<P: PageId> Lru<P> {
   /// Creates a new page table for LRU
   pub fn new(size: usize) -> Self {
     Lru {
       table: vec![LruPage::new(); size],
-      size: size,
       time: 0,
     }
   }
 
-  /// Handles a page request, returns true if page fault occurred
-  pub fn handle_page_request(&mut self, page_request: u64, should_stdout: bool) -> bool {
+  /// Handles a page request, returns if a page fault occurred and which
+  /// page was replaced. Pinned pages are skipped, errors if every frame
+  /// is pinned
+  pub fn handle_page_request(&mut self, page_request: P, pinned: &HashSet<P>)
+    -> Result<Outcome<P>> {
     self.time += 1;
-    // get index of min page
+    // get index of min page that is not pinned
     let (_min_time, min_index) = self.table
       .iter()
       .enumerate()
-      .fold((u64::max_value(), 0), |mut acc, (i, x)| {
-        if x.time < acc.0 {
+      .filter(|&(_, x)| pinned.is_empty()
+        || !x.number.as_ref().is_some_and(|n| pinned.contains(n)))
+      .fold((u64::MAX, None), |mut acc, (i, x)| {
+        if acc.1.is_none() || x.time < acc.0 {
           acc.0 = x.time;
-          acc.1 = i;
+          acc.1 = Some(i);
         }
 
         acc
@@ -66,31 +76,31 @@ impl Lru {
     // entry again if it does exist
     let page_index = self.table
       .iter()
-      .position(|x| x.number == page_request);
+      .position(|x| x.number.as_ref() == Some(&page_request));
 
     // check if in memory / page table
     if page_index.is_none() {
-      if should_stdout {
-        println!("Page {} caused a page fault", page_request);
-      }
+      let min_index = min_index.ok_or_else(|| Error::Paging(format!(
+        "All frames are pinned, cannot load page {}", page_request)))?;
 
       // create a new page entry
       let new_page = LruPage {
-        number: page_request,
+        number: Some(page_request),
         time: self.time,
       };
       
       // replace oldest entry with new one
-      {
+      let outcome = {
         // mutable borrow
         let elem = self.table.get_mut(min_index).unwrap();
-        trace!("SWAP: [{:?} -> {:?}] @ i = {}", *elem, &new_page, min_index);
-        *elem = new_page;
+        Outcome::Fault {
+          evicted: mem::replace(elem, new_page).number,
+          frame: min_index,
+        }
         // mutable borrow ends
-      }
+      };
 
-      debug!("{:?}", self.table);
-      return true;
+      return Ok(outcome);
     }
 
     // update time for existing page
@@ -98,12 +108,43 @@ impl Lru {
       // can unwrap here since vec must contain the item here
       let index = page_index.unwrap();
       let elem = self.table.get_mut(index).unwrap();
-      trace!("ADJUST: #{} time [{} -> {}] @ i = {}",
-        elem.number, elem.time, self.time, index);
       elem.time = self.time;
     }
     
-    debug!("{:?}", self.table);
-    false
+    Ok(Outcome::Hit)
+  }
+
+  /// Checks if a page is in the table without updating its time
+  pub fn contains(&self, page: &P) -> bool {
+    self.table.iter().any(|x| x.number.as_ref() == Some(page))
+  }
+
+  /// Removes a page from the table, the empty frame has the oldest time
+  /// so it is filled first. Returns if the page was in the table
+  pub fn invalidate(&mut self, page: &P) -> bool {
+    match self.table.iter().position(|x| x.number.as_ref() == Some(page)) {
+      Some(index) => {
+        self.table[index] = LruPage::new();
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Gets a snapshot of the page table with the time of each page
+  pub fn state(&self) -> TableState<P> {
+    let frames = self.table
+      .iter()
+      .map(|x| Frame {
+        page: x.number.clone(),
+        referenced: None,
+        time: Some(x.time),
+      })
+      .collect();
+
+    TableState {
+      frames,
+      hand: None,
+    }
   }
 }
@@ -1,20 +1,168 @@
+use error::Result;
+use model::page::PageId;
+use std::collections::HashSet;
+
 pub mod fifo;
 pub mod lru;
+pub mod nru;
 pub mod optimal;
+pub mod random;
 pub mod second_chance;
 
 // struct reexports
 pub use self::fifo::Fifo;
 pub use self::lru::Lru;
+pub use self::nru::Nru;
 pub use self::optimal::Optimal;
+pub use self::random::Random;
 pub use self::second_chance::SecondChance;
 
+/// Checks if an algorithm picks pages to replace at random, randomized
+/// algorithms give different results for different seeds
+pub fn is_randomized(algorithm: &str) -> bool {
+  algorithm == "random" || algorithm == "nru"
+}
+
+/// Parameters of the randomized algorithms
+#[derive(Clone, Copy, Debug)]
+pub struct AlgorithmParams {
+  /// Seed of the random number generator, the same seed gives the same results
+  pub seed: u64,
+  /// Number of page requests between clearing the referenced bits of NRU,
+  /// 0 never clears them
+  pub nru_interval: u64,
+}
+
+impl Default for AlgorithmParams {
+  fn default() -> Self {
+    AlgorithmParams {
+      seed: 0,
+      nru_interval: 100,
+    }
+  }
+}
+
+/// Result of a single page request
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome<P = u64> {
+  /// Page was already in memory
+  Hit,
+  /// Page was not in memory and was loaded into `frame`, replacing the
+  /// `evicted` page if the frame was not empty
+  Fault {
+    evicted: Option<P>,
+    frame: usize,
+  },
+}
+
+impl<P> Outcome<P> {
+  /// Checks if the request resulted in a page fault
+  pub fn is_fault(&self) -> bool {
+    match *self {
+      Outcome::Hit => false,
+      Outcome::Fault { .. } => true,
+    }
+  }
+}
+
+/// A single page table frame, used to display the table contents
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame<P = u64> {
+  /// Page number, None if the frame is empty
+  pub page: Option<P>,
+  /// Referenced bit, only used by second chance and NRU
+  pub referenced: Option<bool>,
+  /// Time of last access, only used by LRU
+  pub time: Option<u64>,
+}
+
+impl<P> Frame<P> {
+  /// Creates a frame without any algorithm specific data
+  pub fn new(page: Option<P>) -> Self {
+    Frame {
+      page,
+      referenced: None,
+      time: None,
+    }
+  }
+}
+
+/// Snapshot of the contents of a page table
+#[derive(Clone, Debug)]
+pub struct TableState<P = u64> {
+  /// Frames in table order
+  pub frames: Vec<Frame<P>>,
+  /// Position of the clock hand for circular tables
+  pub hand: Option<usize>,
+}
+
 /// Enum to hold the different types of page replacement algorithms
-pub enum AlgorithmType {
-  Fifo(Fifo),
-  Lru(Lru),
-  Optimal(Optimal),
-  SecondChance(SecondChance),
+#[derive(Clone)]
+pub enum AlgorithmType<P: PageId = u64> {
+  Fifo(Fifo<P>),
+  Lru(Lru<P>),
+  Nru(Nru<P>),
+  Optimal(Optimal<P>),
+  Random(Random<P>),
+  SecondChance(SecondChance<P>),
+}
+
+impl<P: PageId> AlgorithmType<P> {
+  /// Gets a snapshot of the page table contents
+  pub fn state(&self) -> TableState<P> {
+    match *self {
+      AlgorithmType::Fifo(ref x) => x.state(),
+      AlgorithmType::Lru(ref x) => x.state(),
+      AlgorithmType::Nru(ref x) => x.state(),
+      AlgorithmType::Optimal(ref x) => x.state(),
+      AlgorithmType::Random(ref x) => x.state(),
+      AlgorithmType::SecondChance(ref x) => x.state(),
+    }
+  }
+
+  /// Handles a page request with the algorithm, pinned pages are never
+  /// replaced
+  pub fn handle_page_request(&mut self, page: P, pinned: &HashSet<P>) -> Result<Outcome<P>> {
+    match *self {
+      AlgorithmType::Fifo(ref mut x) => x.handle_page_request(page, pinned),
+      AlgorithmType::Lru(ref mut x) => x.handle_page_request(page, pinned),
+      AlgorithmType::Nru(ref mut x) => x.handle_page_request(page, pinned),
+      AlgorithmType::Optimal(ref mut x) => x.handle_page_request(page, pinned),
+      AlgorithmType::Random(ref mut x) => x.handle_page_request(page, pinned),
+      AlgorithmType::SecondChance(ref mut x) => x.handle_page_request(page, pinned),
+    }
+  }
+
+  /// Checks if a page is in the page table without counting it as a request
+  pub fn contains(&self, page: &P) -> bool {
+    match *self {
+      AlgorithmType::Fifo(ref x) => x.contains(page),
+      AlgorithmType::Lru(ref x) => x.contains(page),
+      AlgorithmType::Nru(ref x) => x.contains(page),
+      AlgorithmType::Optimal(ref x) => x.contains(page),
+      AlgorithmType::Random(ref x) => x.contains(page),
+      AlgorithmType::SecondChance(ref x) => x.contains(page),
+    }
+  }
+
+  /// Marks a page in the page table as written, only used by NRU
+  pub fn mark_modified(&mut self, page: &P) {
+    if let AlgorithmType::Nru(ref mut x) = *self {
+      x.mark_modified(page);
+    }
+  }
+
+  /// Removes a page from the page table, returns if it was in the table
+  pub fn invalidate(&mut self, page: &P) -> bool {
+    match *self {
+      AlgorithmType::Fifo(ref mut x) => x.invalidate(page),
+      AlgorithmType::Lru(ref mut x) => x.invalidate(page),
+      AlgorithmType::Nru(ref mut x) => x.invalidate(page),
+      AlgorithmType::Optimal(ref mut x) => x.invalidate(page),
+      AlgorithmType::Random(ref mut x) => x.invalidate(page),
+      AlgorithmType::SecondChance(ref mut x) => x.invalidate(page),
+    }
+  }
 }
 
 /*
@@ -0,0 +1,168 @@
+use super::{Frame, Outcome, TableState};
+use error::{Error, Result};
+use model::page::PageId;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::collections::HashSet;
+use std::fmt;
+use std::mem;
+
+/// A page table entry for NRU page replacement
+#[derive (Clone)]
+pub struct NruPage<P> {
+  /// Page number, None if the frame is empty
+  number: Option<P>,
+  /// Referenced "bit", cleared every interval
+  referenced: bool,
+  /// Modified "bit", set when the page is written
+  modified: bool,
+}
+
+impl<P> NruPage<P> {
+  fn new() -> Self {
+    NruPage {
+      number: None,
+      referenced: false,
+      modified: false,
+    }
+  }
+
+  /// Class used to pick the page to replace, lower classes are replaced
+  /// first: 0 = not referenced or modified, 1 = modified, 2 = referenced,
+  /// 3 = referenced and modified
+  fn class(&self) -> u8 {
+    (self.referenced as u8) << 1 | self.modified as u8
+  }
+}
+
+impl<P: fmt::Debug> fmt::Debug for NruPage<P> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.number {
+      Some(ref number) => write!(f, "{:?} ", number)?,
+      None => write!(f, "- ")?,
+    }
+
+    write!(f, "({}{})", self.referenced as u8, self.modified as u8)
+  }
+}
+
+/// A page table for NRU (not recently used) page replacement
+#[derive (Clone, Debug)]
+pub struct Nru<P = u64> {
+  /// Vec of page numbers
+  table: Vec<NruPage<P>>,
+  /// Picks the page to replace within the lowest class
+  rng: StdRng,
+  /// Number of page requests between clearing referenced bits, 0 never clears
+  interval: u64,
+  /// Number of page requests handled
+  requests: u64,
+}
+
+impl<P: PageId> Nru<P> {
+  pub fn new(size: usize, seed: u64, interval: u64) -> Self {
+    Nru {
+      table: vec![NruPage::new(); size],
+      rng: StdRng::seed_from_u64(seed),
+      interval,
+      requests: 0,
+    }
+  }
+
+  /// Handles a page request, returns if a page fault occurred and which
+  /// page was replaced. Pinned pages are skipped, errors if every frame
+  /// is pinned
+  pub fn handle_page_request(&mut self, page_request: P, pinned: &HashSet<P>)
+    -> Result<Outcome<P>> {
+    // clear referenced bits periodically, like a clock interrupt would
+    self.requests += 1;
+    if self.interval > 0 && self.requests.is_multiple_of(self.interval) {
+      for page in self.table.iter_mut() {
+        page.referenced = false;
+      }
+    }
+
+    if let Some(page) = self.table.iter_mut().find(|x| x.number.as_ref() == Some(&page_request)) {
+      page.referenced = true;
+      return Ok(Outcome::Hit);
+    }
+
+    // fill empty frames first, otherwise replace a random page of the
+    // lowest class. Empty frames are never pinned
+    let frame = match self.table.iter().position(|x| x.number.is_none()) {
+      Some(frame) => frame,
+      None => {
+        let unpinned = self.table
+          .iter()
+          .enumerate()
+          .filter(|&(_, x)| pinned.is_empty()
+            || !x.number.as_ref().is_some_and(|n| pinned.contains(n)));
+        let lowest = match unpinned.clone().map(|(_, x)| x.class()).min() {
+          Some(lowest) => lowest,
+          None => return Err(Error::Paging(format!(
+            "All frames are pinned, cannot load page {}", page_request))),
+        };
+
+        let candidates: Vec<usize> = unpinned
+          .filter(|&(_, x)| x.class() == lowest)
+          .map(|(i, _)| i)
+          .collect();
+        candidates[self.rng.gen_range(0..candidates.len())]
+      },
+    };
+
+    let page = &mut self.table[frame];
+    let replaced = mem::replace(page, NruPage {
+      number: Some(page_request),
+      referenced: true,
+      modified: false,
+    });
+    let outcome = Outcome::Fault {
+      evicted: replaced.number,
+      frame,
+    };
+
+    Ok(outcome)
+  }
+
+  /// Sets the modified bit of a page in the table
+  pub fn mark_modified(&mut self, page: &P) {
+    if let Some(x) = self.table.iter_mut().find(|x| x.number.as_ref() == Some(page)) {
+      x.modified = true;
+    }
+  }
+
+  /// Checks if a page is in the table without setting its referenced bit
+  pub fn contains(&self, page: &P) -> bool {
+    self.table.iter().any(|x| x.number.as_ref() == Some(page))
+  }
+
+  /// Removes a page from the table, the empty frame is filled by the next
+  /// page fault. Returns if the page was in the table
+  pub fn invalidate(&mut self, page: &P) -> bool {
+    match self.table.iter().position(|x| x.number.as_ref() == Some(page)) {
+      Some(index) => {
+        self.table[index] = NruPage::new();
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Gets a snapshot of the page table with referenced bits
+  pub fn state(&self) -> TableState<P> {
+    let frames = self.table
+      .iter()
+      .map(|x| Frame {
+        page: x.number.clone(),
+        referenced: Some(x.referenced),
+        time: None,
+      })
+      .collect();
+
+    TableState {
+      frames,
+      hand: None,
+    }
+  }
+}
@@ -1,101 +1,113 @@
-use parking_lot::RwLock;
+use super::{Frame, Outcome, TableState};
+use error::{Error, Result};
+use model::page::PageId;
+use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::sync::Arc;
-use std::process;
-
-// This optimal implementation is **not** working correctly
-// was intended to be used to compare with the other algorithms
 
 /// A page table entry for Optimal page replacement
-#[derive (Debug)]
-pub struct Optimal {
-  /// Vec of page numbers
-  table: Vec<u64>,
-  /// Full future page requests
-  page_requests: Arc<RwLock<Vec<String>>>,
-  /// Size of page table
-  size: usize,
+#[derive (Clone, Debug)]
+pub struct Optimal<P = u64> {
+  /// Vec of page numbers, None if the frame is empty
+  table: Vec<Option<P>>,
+  /// Every page request of the trace in order, without other lines
+  page_requests: Arc<Vec<P>>,
   /// Position of future page requests
   index: usize,
 }
 
-impl Optimal {
-  pub fn new(size: usize, page_requests: Option<Arc<RwLock<Vec<String>>>>) -> Self {
-    if page_requests.is_none() {
-      error!("Must run optimal with page requests as an input file");
-      process::exit(1);
-    }
+impl<P: PageId> Optimal<P> {
+  /// Creates a table looking ahead in the page requests, errors without
+  /// them since the future is unknown
+  pub fn new(size: usize, page_requests: Option<Arc<Vec<P>>>) -> Result<Self> {
+    let page_requests = page_requests.ok_or_else(|| Error::Algorithm(
+      "Must run optimal with page requests as an input file".to_string()))?;
 
-    Optimal {
-      table: vec![0; size], // initialize vec with size 0s
-      page_requests: page_requests.unwrap(),
-      size,
+    Ok(Optimal {
+      table: vec![None; size], // initialize vec with size empty frames
+      page_requests,
       index: 0,
-    }
+    })
   }
 
-  /// Handles a page request, returns true if page fault occurred
-  pub fn handle_page_request(&mut self, page_request: u64, should_stdout: bool) -> bool {
+  /// Handles a page request, returns if a page fault occurred and which
+  /// page was replaced. Pinned pages are skipped, errors if every frame
+  /// is pinned
+  pub fn handle_page_request(&mut self, page_request: P, pinned: &HashSet<P>)
+    -> Result<Outcome<P>> {
     // increment position in "future" page requests
     self.index += 1;
 
-    if !self.table.contains(&page_request) {
-      if should_stdout {
-        println!("Page {} caused a page fault", page_request);
-      }
-
-      // search for any pages with number 0 (all initialized to 0)
+    if !self.contains(&page_request) {
+      // fill empty frames first, empty frames are never pinned
       let free_page_index = self.table
         .iter()
-        .position(|&x| x == 0);
+        .position(|x| x.is_none());
 
-      // contains pages with 0
-      // means not full since initialized to 0 and does not accept #0
-      if let Some(index) = free_page_index {
-        let elem = self.table.get_mut(index).unwrap();
-        trace!("Added page {}", page_request);
-        *elem = page_request;
-      } else {
-        // table is full
-        // search for furthest page
-        let mut furthest_distance = 0;
-        let mut furthest_index = 0;
-        let page_requests_slice = &self.page_requests.read()[self.index..];
-        for (i, page) in self.table.iter().enumerate() {
-          // no pages should be 0 since full, don't have to check
-          // iterate over future page requests from current position
-          // to find the furthest distance
-          let distance = page_requests_slice
+      let index = match free_page_index {
+        Some(index) => index,
+        None => {
+          // table is full
+          // search for furthest page that is not pinned, pages never used
+          // again are the furthest. Ties go to the lowest page number so the
+          // choice does not depend on the table size
+          let future = self.page_requests.get(self.index..).unwrap_or(&[]);
+          let furthest_index = self.table
             .iter()
-            .position(|future_page| future_page
-              .parse::<u64>()
-              .unwrap_or(0) == *page
-            );
+            .enumerate()
+            .filter_map(|(i, page)| page.as_ref().map(|page| (i, page)))
+            .filter(|&(_, page)| pinned.is_empty() || !pinned.contains(page))
+            .max_by_key(|&(_, page)| {
+              // iterate over future page requests from current position
+              // to find the furthest distance
+              let distance = future
+                .iter()
+                .position(|future_page| future_page == page)
+                .unwrap_or(usize::MAX);
 
-          if let Some(distance) = distance {
-            // check if this is furthest page
-            if distance > furthest_distance {
-              furthest_distance = distance;
-              furthest_index = i;
-            }
-          } else {
-            // cannot find in future requests,
-            // won't ever be used again so replace this one
-            furthest_index = i;
-            break;
-          }
-        }
+              (distance, Reverse(page))
+            })
+            .map(|(i, _)| i);
 
-        // replace page with furthest
-        let furthest_elem = self.table.get_mut(furthest_index).unwrap();
-        trace!("Replaced {} -> {}", *furthest_elem, page_request);
-        *furthest_elem = page_request;
-      }
+          furthest_index.ok_or_else(|| Error::Paging(format!(
+            "All frames are pinned, cannot load page {}", page_request)))?
+        },
+      };
 
-      debug!("{:?}", self.table);
-      return true;
+      // replace page with furthest, or fill the empty frame
+      let outcome = Outcome::Fault {
+        evicted: self.table[index].replace(page_request),
+        frame: index,
+      };
+
+      return Ok(outcome);
     }
-    debug!("{:?}", self.table);
 
-    false
+    Ok(Outcome::Hit)
+  }
+
+  /// Checks if a page is in the table without counting it as a request
+  pub fn contains(&self, page: &P) -> bool {
+    self.table.iter().any(|x| x.as_ref() == Some(page))
+  }
+
+  /// Removes a page from the table, empty frames are filled first.
+  /// Returns if the page was in the table
+  pub fn invalidate(&mut self, page: &P) -> bool {
+    match self.table.iter().position(|x| x.as_ref() == Some(page)) {
+      Some(index) => {
+        self.table[index] = None;
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Gets a snapshot of the page table
+  pub fn state(&self) -> TableState<P> {
+    TableState {
+      frames: self.table.iter().map(|page| Frame::new(page.clone())).collect(),
+      hand: None,
+    }
   }
 }
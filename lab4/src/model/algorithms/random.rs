@@ -0,0 +1,84 @@
+use super::{Frame, Outcome, TableState};
+use error::{Error, Result};
+use model::page::PageId;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::collections::HashSet;
+
+/// A page table for random page replacement
+#[derive (Clone, Debug)]
+pub struct Random<P = u64> {
+  /// Vec of page numbers, None if the frame is empty
+  table: Vec<Option<P>>,
+  /// Picks the page to replace, the same seed replaces the same pages
+  rng: StdRng,
+}
+
+impl<P: PageId> Random<P> {
+  pub fn new(size: usize, seed: u64) -> Self {
+    Random {
+      table: vec![None; size], // initialize vec with size empty frames
+      rng: StdRng::seed_from_u64(seed),
+    }
+  }
+
+  /// Handles a page request, returns if a page fault occurred and which
+  /// page was replaced. Pinned pages are skipped, errors if every frame
+  /// is pinned
+  pub fn handle_page_request(&mut self, page_request: P, pinned: &HashSet<P>)
+    -> Result<Outcome<P>> {
+    if self.contains(&page_request) {
+      return Ok(Outcome::Hit);
+    }
+
+    // fill empty frames first, empty frames are never pinned
+    let frame = match self.table.iter().position(|x| x.is_none()) {
+      Some(frame) => frame,
+      None if pinned.is_empty() => self.rng.gen_range(0..self.table.len()),
+      None => {
+        let unpinned: Vec<usize> = (0..self.table.len())
+          .filter(|&i| !self.table[i].as_ref().is_some_and(|x| pinned.contains(x)))
+          .collect();
+
+        if unpinned.is_empty() {
+          return Err(Error::Paging(format!(
+            "All frames are pinned, cannot load page {}", page_request)));
+        }
+
+        unpinned[self.rng.gen_range(0..unpinned.len())]
+      },
+    };
+
+    let outcome = Outcome::Fault {
+      evicted: self.table[frame].replace(page_request),
+      frame,
+    };
+
+    Ok(outcome)
+  }
+
+  /// Checks if a page is in the table without counting it as a request
+  pub fn contains(&self, page: &P) -> bool {
+    self.table.iter().any(|x| x.as_ref() == Some(page))
+  }
+
+  /// Removes a page from the table, the empty frame is filled by the next
+  /// page fault. Returns if the page was in the table
+  pub fn invalidate(&mut self, page: &P) -> bool {
+    match self.table.iter().position(|x| x.as_ref() == Some(page)) {
+      Some(index) => {
+        self.table[index] = None;
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Gets a snapshot of the page table
+  pub fn state(&self) -> TableState<P> {
+    TableState {
+      frames: self.table.iter().map(|page| Frame::new(page.clone())).collect(),
+      hand: None,
+    }
+  }
+}
@@ -1,91 +1,110 @@
+use super::{Frame, Outcome, TableState};
+use error::{Error, Result};
+use model::page::PageId;
+use std::collections::HashSet;
 use std::fmt;
 
 /// A page table entry for second chance page replacement
 #[derive (Clone)]
-pub struct SecondChancePage {
-  /// Page number
-  number: u64,
+pub struct SecondChancePage<P> {
+  /// Page number, None if the frame is empty
+  number: Option<P>,
   /// Referenced "bit"
   referenced: bool,
 }
 
-impl SecondChancePage {
+impl<P> SecondChancePage<P> {
   fn new() -> Self {
     SecondChancePage {
-      number: 0,
+      number: None,
       referenced: false,
     }
   }
 }
 
-impl fmt::Debug for SecondChancePage {
+impl<P: fmt::Debug> fmt::Debug for SecondChancePage<P> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let number = match self.number {
+      Some(ref number) => format!("{:?}", number),
+      None => "-".to_string(),
+    };
+
     if self.referenced {
       // blue number referenced
-      write!(f, "\x1b[0;36m{}\x1b[0;0m", self.number)
+      write!(f, "\x1b[0;36m{}\x1b[0;0m", number)
     } else {
       // red number unreferenced
-      write!(f, "\x1b[0;31m{}\x1b[0;0m", self.number)
+      write!(f, "\x1b[0;31m{}\x1b[0;0m", number)
     }
   }
 }
 
 /// A page table for second chance page replacement
-#[derive (Debug)]
-pub struct SecondChance {
+#[derive (Clone, Debug)]
+pub struct SecondChance<P = u64> {
   /// Vec of page numbers
-  table: Vec<SecondChancePage>,
+  table: Vec<SecondChancePage<P>>,
   /// Size of page table
   size: usize,
   /// Index position
   index: usize,
 }
 
-impl SecondChance {
+impl<P: PageId> SecondChance<P> {
   pub fn new(size: usize) -> Self {
     SecondChance {
       table: vec![SecondChancePage::new(); size],
-      size: size,
+      size,
       index: 0,      
     }
   }
 
-  /// Handles a page request, returns true if page fault occurred
-  pub fn handle_page_request(&mut self, page_request: u64, should_stdout: bool) -> bool {
+  /// Handles a page request, returns if a page fault occurred and which
+  /// page was replaced. Pinned pages are skipped, errors if every frame
+  /// is pinned
+  pub fn handle_page_request(&mut self, page_request: P, pinned: &HashSet<P>)
+    -> Result<Outcome<P>> {
     // get page index, returns None if doesn't exist in table
     let page_index = self.table
       .iter()
-      .position(|x| x.number == page_request);
+      .position(|x| x.number.as_ref() == Some(&page_request));
     
     // doesn't contain page number, not in memory
     if page_index.is_none() {
-      // big bottleneck!!
-      if should_stdout {
-        println!("Page number {} caused a page fault.", page_request);
+      // the clock would never stop if every page is pinned
+      if !pinned.is_empty()
+        && self.table.iter().all(|x| x.number.as_ref().is_some_and(|n| pinned.contains(n))) {
+        return Err(Error::Paging(format!(
+          "All frames are pinned, cannot load page {}", page_request)));
       }
 
-      loop {
+      let outcome = loop {
         // safe to unwrap, vec is initialized to be full and self.index is circular
         let page = self.table
           .get_mut(self.index)
           .unwrap();
         
         // move to next page, circular loop
+        let frame = self.index;
         self.index = (self.index + 1) % self.size;
+
+        // pinned pages keep their referenced bit
+        if !pinned.is_empty() && page.number.as_ref().is_some_and(|n| pinned.contains(n)) {
+          continue;
+        }
         
         if !page.referenced {
           // replace page with new page request #, referenced should still be false
-          trace!("SWAP: {} -> {}", page.number, page_request);
-          page.number = page_request;
-          break;
+          break Outcome::Fault {
+            evicted: page.number.replace(page_request),
+            frame,
+          };
         }
 
-        trace!("RESET: {}", page.number);
         page.referenced = false;
-      }
+      };
 
-      debug!("{:?}", self.table);
-      return true;
+      return Ok(outcome);
     }
     // self.index = (self.index + 1) % self.size;
 
@@ -95,11 +114,44 @@ impl SecondChance {
     // update page to be referenced
     {
       let page = self.table.get_mut(index).unwrap();
-      trace!("UPDATE: {:?}", page);
       page.referenced = true;
     }
 
-    debug!("{:?}", self.table);
-    false
+    Ok(Outcome::Hit)
+  }
+
+  /// Checks if a page is in the table without setting its referenced bit
+  pub fn contains(&self, page: &P) -> bool {
+    self.table.iter().any(|x| x.number.as_ref() == Some(page))
+  }
+
+  /// Removes a page from the table, the empty frame is unreferenced so it
+  /// is filled once the hand reaches it. Returns if the page was in the table
+  pub fn invalidate(&mut self, page: &P) -> bool {
+    match self.table.iter().position(|x| x.number.as_ref() == Some(page)) {
+      Some(index) => {
+        self.table[index] = SecondChancePage::new();
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Gets a snapshot of the page table with referenced bits, the hand points
+  /// to the next page to be checked
+  pub fn state(&self) -> TableState<P> {
+    let frames = self.table
+      .iter()
+      .map(|x| Frame {
+        page: x.number.clone(),
+        referenced: Some(x.referenced),
+        time: None,
+      })
+      .collect();
+
+    TableState {
+      frames,
+      hand: Some(self.index),
+    }
   }
 }
@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Replacement policy of an object cache
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CachePolicy {
+  /// Evicts the least recently used objects until the new object fits
+  Lru,
+  /// Evicts the objects loaded first until the new object fits
+  Fifo,
+  /// LRU-MIN, evicts the least recently used object at least as large as
+  /// the new object, halving the size until one is found. Fewer objects
+  /// are evicted for small objects
+  SizeLru,
+  /// Greedy Dual Size Frequency, evicts the object with the lowest
+  /// priority `L + frequency / size` where L is the priority of the last
+  /// evicted object. Favours small and frequently requested objects
+  Gdsf,
+}
+
+impl CachePolicy {
+  /// Gets the policy of a name given on the command line
+  pub fn new(name: &str) -> Self {
+    match name {
+      "lru" => CachePolicy::Lru,
+      "fifo" => CachePolicy::Fifo,
+      "size_lru" => CachePolicy::SizeLru,
+      "gdsf" => CachePolicy::Gdsf,
+      _ => unreachable!(),
+    }
+  }
+}
+
+/// Result of a single object request
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CacheOutcome {
+  Hit,
+  /// The object was loaded, evicting objects if the cache was full
+  Miss,
+  /// The object is larger than the cache and was not loaded
+  Bypass,
+}
+
+/// A cached object
+struct CachedObject {
+  size: u64,
+  /// Number of requests since the object was loaded
+  frequency: u64,
+  /// Position in the eviction order
+  order: (u64, u64),
+}
+
+/// A cache of objects with different sizes and a capacity in bytes
+pub struct ObjectCache<K> {
+  policy: CachePolicy,
+  capacity: u64,
+  /// Bytes used by cached objects
+  used: u64,
+  objects: HashMap<K, CachedObject>,
+  /// Objects in the order they are evicted, by priority for GDSF and
+  /// then by the request count of their last update
+  order: BTreeMap<(u64, u64), K>,
+  /// GDSF inflation value, the priority of the last evicted object
+  inflation: f64,
+  requests: u64,
+  hits: u64,
+  bytes: u64,
+  hit_bytes: u64,
+  evictions: u64,
+  bypassed: u64,
+}
+
+impl<K: Clone + Eq + Hash> ObjectCache<K> {
+  pub fn new(policy: CachePolicy, capacity: u64) -> Self {
+    ObjectCache {
+      policy,
+      capacity,
+      used: 0,
+      objects: HashMap::new(),
+      order: BTreeMap::new(),
+      inflation: 0.0,
+      requests: 0,
+      hits: 0,
+      bytes: 0,
+      hit_bytes: 0,
+      evictions: 0,
+      bypassed: 0,
+    }
+  }
+
+  /// Position of an object in the eviction order. Priorities are never
+  /// negative so their bits sort the same as the floats
+  fn order_key(&self, size: u64, frequency: u64) -> (u64, u64) {
+    match self.policy {
+      CachePolicy::Gdsf => {
+        let priority = self.inflation + frequency as f64 / size.max(1) as f64;
+        (priority.to_bits(), self.requests)
+      },
+      _ => (0, self.requests),
+    }
+  }
+
+  /// Requests an object, an object requested with a different size than
+  /// the cached one was changed and is loaded again as a miss
+  pub fn request(&mut self, key: &K, size: u64) -> CacheOutcome {
+    self.requests += 1;
+    self.bytes += size;
+
+    match self.objects.get(key).map(|x| x.size) {
+      Some(cached) if cached == size => {
+        self.hits += 1;
+        self.hit_bytes += size;
+        self.touch(key);
+        return CacheOutcome::Hit;
+      },
+      Some(_) => {
+        self.remove(key);
+      },
+      None => {},
+    }
+
+    if size > self.capacity {
+      self.bypassed += 1;
+      return CacheOutcome::Bypass;
+    }
+
+    while self.used + size > self.capacity {
+      self.evict(size);
+      self.evictions += 1;
+    }
+
+    let order = self.order_key(size, 1);
+    self.order.insert(order, key.clone());
+    self.objects.insert(key.clone(), CachedObject {
+      size,
+      frequency: 1,
+      order,
+    });
+    self.used += size;
+
+    CacheOutcome::Miss
+  }
+
+  /// Updates the eviction order of a cached object after a hit
+  fn touch(&mut self, key: &K) {
+    if self.policy == CachePolicy::Fifo {
+      return;
+    }
+
+    // safe to unwrap, only called for cached objects
+    let (size, frequency, old) = {
+      let object = self.objects.get_mut(key).unwrap();
+      object.frequency += 1;
+      (object.size, object.frequency, object.order)
+    };
+
+    let order = self.order_key(size, frequency);
+    self.order.remove(&old);
+    self.order.insert(order, key.clone());
+    self.objects.get_mut(key).unwrap().order = order;
+  }
+
+  /// Removes an object from the cache
+  fn remove(&mut self, key: &K) {
+    if let Some(object) = self.objects.remove(key) {
+      self.order.remove(&object.order);
+      self.used -= object.size;
+    }
+  }
+
+  /// Evicts a single object to make room for an object of the given size
+  fn evict(&mut self, size: u64) {
+    let victim = match self.policy {
+      CachePolicy::SizeLru => {
+        // the cache is not empty while the new object doesn't fit, a
+        // threshold of 0 always finds an object
+        let mut threshold = size;
+        loop {
+          let found = self.order
+            .values()
+            .find(|x| self.objects[*x].size >= threshold)
+            .cloned();
+
+          match found {
+            Some(victim) => break victim,
+            None => threshold /= 2,
+          }
+        }
+      },
+      // safe to unwrap, the cache is not empty while the new object doesn't fit
+      _ => self.order.values().next().cloned().unwrap(),
+    };
+
+    if self.policy == CachePolicy::Gdsf {
+      self.inflation = f64::from_bits(self.objects[&victim].order.0);
+    }
+
+    self.remove(&victim);
+  }
+
+  pub fn capacity(&self) -> u64 {
+    self.capacity
+  }
+
+  /// Number of objects requested
+  pub fn requests(&self) -> u64 {
+    self.requests
+  }
+
+  /// Number of requested objects that were cached
+  pub fn hits(&self) -> u64 {
+    self.hits
+  }
+
+  /// Total size of requested objects in bytes
+  pub fn bytes(&self) -> u64 {
+    self.bytes
+  }
+
+  /// Total size of requested objects that were cached in bytes
+  pub fn hit_bytes(&self) -> u64 {
+    self.hit_bytes
+  }
+
+  /// Number of objects evicted to make room for others
+  pub fn evictions(&self) -> u64 {
+    self.evictions
+  }
+
+  /// Number of requests for objects larger than the cache
+  pub fn bypassed(&self) -> u64 {
+    self.bypassed
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use model::simulation::Simulation;
+  use proptest::prelude::*;
+  use super::*;
+
+  proptest! {
+    #[test]
+    fn unit_size_objects_match_page_frames(trace in prop::collection::vec(0u64..12, 0..200),
+      table_size in 1usize..=8) {
+      for algorithm in ["lru", "fifo"].iter() {
+        let mut cache = ObjectCache::new(CachePolicy::new(algorithm), table_size as u64);
+        let mut sim = Simulation::new(table_size, algorithm, None).unwrap();
+        for &page in &trace {
+          cache.request(&page, 1);
+          sim.request(page).unwrap();
+        }
+        prop_assert_eq!(cache.requests() - cache.hits(), sim.num_misses());
+      }
+    }
+  }
+
+  #[test]
+  fn object_cache_examples() {
+    let trace = [("a", 100), ("b", 200), ("c", 50), ("a", 100), ("d", 300), ("a", 100), ("c", 50)];
+    let run = |policy| {
+      let mut cache = ObjectCache::new(policy, 400);
+      let outcomes: Vec<CacheOutcome> = trace.iter()
+        .map(|&(key, size)| cache.request(&key, size))
+        .collect();
+      (outcomes, cache.hit_bytes(), cache.evictions())
+    };
+
+    // d evicts b and c, then a evicts d
+    let (outcomes, hit_bytes, evictions) = run(CachePolicy::Lru);
+    assert_eq!(outcomes[6], CacheOutcome::Miss);
+    assert_eq!((hit_bytes, evictions), (200, 3));
+
+    // d evicts b and a, the objects at least as large as half its size
+    let (outcomes, hit_bytes, evictions) = run(CachePolicy::SizeLru);
+    assert_eq!(outcomes[5], CacheOutcome::Miss);
+    assert_eq!(outcomes[6], CacheOutcome::Hit);
+    assert_eq!((hit_bytes, evictions), (150, 3));
+
+    // d evicts the large b, then c which ties with a requested twice and
+    // was requested less recently. d has the lowest priority once c returns
+    let (outcomes, hit_bytes, evictions) = run(CachePolicy::Gdsf);
+    assert_eq!(outcomes[5], CacheOutcome::Hit);
+    assert_eq!(outcomes[6], CacheOutcome::Miss);
+    assert_eq!((hit_bytes, evictions), (200, 3));
+
+    let mut cache = ObjectCache::new(CachePolicy::Lru, 400);
+    assert_eq!(cache.request(&"huge", 1000), CacheOutcome::Bypass);
+    assert_eq!(cache.request(&"huge", 1000), CacheOutcome::Bypass);
+  }
+}
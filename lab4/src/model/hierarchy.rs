@@ -0,0 +1,179 @@
+use error::Result;
+use model::algorithms::Outcome;
+use model::simulation::Simulation;
+use std::sync::Arc;
+
+/// Size, algorithm and access latency of a single level
+pub struct LevelOptions<'a> {
+  pub size: usize,
+  pub algorithm: &'a str,
+  /// Time to look up a page in this level in nanoseconds
+  pub latency_ns: f64,
+}
+
+/// Options for a memory hierarchy, levels are checked in order:
+/// TLB, page frames, secondary level and finally the backing store
+pub struct HierarchyOptions<'a> {
+  pub tlb: Option<LevelOptions<'a>>,
+  pub memory: LevelOptions<'a>,
+  pub secondary: Option<LevelOptions<'a>>,
+  /// Time to load a page from the backing store in nanoseconds
+  pub fault_ns: f64,
+}
+
+/// A single level of the hierarchy
+pub struct Level {
+  pub algorithm: String,
+  pub size: usize,
+  pub latency_ns: f64,
+  sim: Simulation,
+}
+
+impl Level {
+  fn new(options: &LevelOptions, page_requests: Option<Arc<Vec<u64>>>) -> Result<Self> {
+    Ok(Level {
+      algorithm: options.algorithm.to_string(),
+      size: options.size,
+      latency_ns: options.latency_ns,
+      sim: Simulation::new(options.size, options.algorithm, page_requests)?,
+    })
+  }
+
+  /// Number of requests that reached this level
+  pub fn requests(&self) -> u64 {
+    self.sim.num_requests()
+  }
+
+  /// Number of requests found in this level
+  pub fn hits(&self) -> u64 {
+    self.sim.num_requests() - self.sim.num_misses()
+  }
+}
+
+/// A TLB in front of the page frames, optionally backed by a secondary level
+/// before the backing store. Pages are loaded into every level they missed
+pub struct Hierarchy {
+  pub tlb: Option<Level>,
+  pub memory: Level,
+  pub secondary: Option<Level>,
+  pub fault_ns: f64,
+  /// Time taken by all requests in nanoseconds
+  total_ns: f64,
+}
+
+impl Hierarchy {
+  /// Creates a hierarchy, the page requests are only needed if the page
+  /// frames use optimal
+  pub fn new(options: &HierarchyOptions, page_requests: Option<Arc<Vec<u64>>>)
+    -> Result<Self> {
+    Ok(Hierarchy {
+      tlb: options.tlb.as_ref().map(|x| Level::new(x, None)).transpose()?,
+      memory: Level::new(&options.memory, page_requests)?,
+      secondary: options.secondary.as_ref().map(|x| Level::new(x, None)).transpose()?,
+      fault_ns: options.fault_ns,
+      total_ns: 0.0,
+    })
+  }
+
+  /// Runs a single page request through the hierarchy, returns the time
+  /// it took in nanoseconds
+  pub fn request(&mut self, page: u64) -> Result<f64> {
+    let mut ns = 0.0;
+
+    // a TLB miss walks the page table, which is an extra memory access
+    let memory_ns = self.memory.latency_ns;
+    if let Some(ref mut tlb) = self.tlb {
+      ns += tlb.latency_ns;
+      if tlb.sim.request(page)?.is_fault() {
+        ns += memory_ns;
+      }
+    }
+
+    ns += self.memory.latency_ns;
+    let memory = self.memory.sim.request(page)?;
+
+    // evicted pages are no longer mapped, remove them from the TLB
+    if let Outcome::Fault { evicted: Some(evicted), .. } = memory {
+      if let Some(ref mut tlb) = self.tlb {
+        tlb.sim.invalidate(&evicted);
+      }
+    }
+
+    if memory.is_fault() {
+      let mut secondary_hit = false;
+      if let Some(ref mut secondary) = self.secondary {
+        ns += secondary.latency_ns;
+        secondary_hit = !secondary.sim.request(page)?.is_fault();
+      }
+
+      if !secondary_hit {
+        ns += self.fault_ns;
+      }
+    }
+
+    self.total_ns += ns;
+    Ok(ns)
+  }
+
+  /// Number of page requests made
+  pub fn num_requests(&self) -> u64 {
+    self.memory.requests()
+  }
+
+  /// Number of requests that were not in memory or the secondary level
+  pub fn backing_requests(&self) -> u64 {
+    match self.secondary {
+      Some(ref secondary) => secondary.requests() - secondary.hits(),
+      None => self.memory.requests() - self.memory.hits(),
+    }
+  }
+
+  /// Time taken by all requests in nanoseconds
+  pub fn total_ns(&self) -> f64 {
+    self.total_ns
+  }
+
+  /// Average time of a page request in nanoseconds
+  pub fn effective_access_ns(&self) -> f64 {
+    if self.num_requests() == 0 {
+      return 0.0;
+    }
+
+    self.total_ns / self.num_requests() as f64
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hand_computed_trace() {
+    let options = HierarchyOptions {
+      tlb: Some(LevelOptions { size: 2, algorithm: "fifo", latency_ns: 1.0 }),
+      memory: LevelOptions { size: 2, algorithm: "lru", latency_ns: 100.0 },
+      secondary: Some(LevelOptions { size: 4, algorithm: "lru", latency_ns: 1000.0 }),
+      fault_ns: 10000.0,
+    };
+    let mut hierarchy = Hierarchy::new(&options, None).unwrap();
+
+    let times = [1, 2, 1, 3, 2, 3]
+      .iter()
+      .map(|&x| hierarchy.request(x).unwrap())
+      .collect::<Vec<_>>();
+
+    // 3 evicts 1 from the TLB but 2 from memory, so 2 is invalidated in
+    // the TLB and misses it before being found in the secondary level
+    assert_eq!(times, vec![11201.0, 11201.0, 101.0, 11201.0, 1201.0, 101.0]);
+
+    let tlb = hierarchy.tlb.as_ref().unwrap();
+    assert_eq!((tlb.requests(), tlb.hits()), (6, 2));
+    assert_eq!((hierarchy.memory.requests(), hierarchy.memory.hits()), (6, 2));
+    let secondary = hierarchy.secondary.as_ref().unwrap();
+    assert_eq!((secondary.requests(), secondary.hits()), (4, 1));
+    assert_eq!(hierarchy.backing_requests(), 3);
+
+    assert_eq!(hierarchy.total_ns(), 35006.0);
+    assert_eq!(hierarchy.effective_access_ns(), 35006.0 / 6.0);
+  }
+}
@@ -1,2 +1,11 @@
 pub mod algorithms;
+pub mod cache;
+pub mod hierarchy;
+pub mod observer;
+pub mod page;
+pub mod prefetch;
+pub mod report;
 pub mod simulation;
+
+#[cfg(test)]
+mod tests;
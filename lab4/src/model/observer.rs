@@ -0,0 +1,240 @@
+use log::Level;
+use model::algorithms::TableState;
+use model::page::PageId;
+use model::simulation::{PageRequest, SimulationResult};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// Receives the progress of a simulation. Simulations don't write any
+/// output themselves, printing or collecting statistics is left to the
+/// observers
+pub trait Observer<P: PageId = u64>: Send {
+  /// Called when a requested page is already in the page table
+  fn on_hit(&mut self, _request: &PageRequest<P>) {}
+
+  /// Called when a requested page is loaded into `frame`, after the page
+  /// it replaces is evicted
+  fn on_fault(&mut self, _request: &PageRequest<P>, _frame: usize) {}
+
+  /// Called when a page is replaced, including pages replaced by prefetched
+  /// pages. `age` is the number of page requests since the page was loaded
+  fn on_evict(&mut self, _page: &P, _frame: usize, _age: u64) {}
+
+  /// Checks if the observer needs the page table after every request, the
+  /// table is only copied when an observer needs it
+  fn wants_table(&self) -> bool {
+    false
+  }
+
+  /// Called with the page table after every page request if `wants_table`
+  fn on_table(&mut self, _state: &TableState<P>) {}
+
+  /// Called when the results of a finished simulation are collected
+  fn on_finish(&mut self, _result: &SimulationResult) {}
+}
+
+/// Shared observer, keeps a reference to read what it collected after the
+/// simulation
+impl<P: PageId, O: Observer<P>> Observer<P> for Arc<Mutex<O>> {
+  fn on_hit(&mut self, request: &PageRequest<P>) {
+    self.lock().on_hit(request);
+  }
+
+  fn on_fault(&mut self, request: &PageRequest<P>, frame: usize) {
+    self.lock().on_fault(request, frame);
+  }
+
+  fn on_evict(&mut self, page: &P, frame: usize, age: u64) {
+    self.lock().on_evict(page, frame, age);
+  }
+
+  fn wants_table(&self) -> bool {
+    self.lock().wants_table()
+  }
+
+  fn on_table(&mut self, state: &TableState<P>) {
+    self.lock().on_table(state);
+  }
+
+  fn on_finish(&mut self, result: &SimulationResult) {
+    self.lock().on_finish(result);
+  }
+}
+
+/// Prints page faults and the results of a simulation to stdout
+pub struct StdoutObserver;
+
+impl<P: PageId> Observer<P> for StdoutObserver {
+  fn on_fault(&mut self, request: &PageRequest<P>, _frame: usize) {
+    println!("Page {} caused a page fault", request.page);
+  }
+
+  fn on_finish(&mut self, result: &SimulationResult) {
+    println!("Hit rate: {:.5}", result.hit_rate);
+    println!("Effective access time: {:.3} ns", result.effective_access_ns);
+    println!("Total time: {:.3} ms", result.total_ns / 1e6);
+  }
+}
+
+/// Logs every page request at trace level, the page table after every
+/// request and a summary at debug level
+#[derive(Default)]
+pub struct LogObserver {
+  requests: u64,
+  hits: u64,
+  evictions: u64,
+  /// Sum of the ages of evicted pages
+  evicted_age: u64,
+}
+
+impl<P: PageId> Observer<P> for LogObserver {
+  fn on_hit(&mut self, request: &PageRequest<P>) {
+    trace!("Page {} hit", request.page);
+    self.requests += 1;
+    self.hits += 1;
+  }
+
+  fn on_fault(&mut self, request: &PageRequest<P>, frame: usize) {
+    trace!("Page {} loaded into frame {}", request.page, frame);
+    self.requests += 1;
+  }
+
+  fn on_evict(&mut self, page: &P, frame: usize, age: u64) {
+    trace!("Page {} evicted from frame {} after {} requests", page, frame, age);
+    self.evictions += 1;
+    self.evicted_age += age;
+  }
+
+  fn wants_table(&self) -> bool {
+    log_enabled!(Level::Debug)
+  }
+
+  /// Logs the page of every frame, `*` marks a referenced bit and `@` the
+  /// time of the last access
+  fn on_table(&mut self, state: &TableState<P>) {
+    let frames: Vec<String> = state.frames
+      .iter()
+      .map(|frame| {
+        let mut text = match frame.page {
+          Some(ref page) => page.to_string(),
+          None => return "-".to_string(),
+        };
+        if frame.referenced == Some(true) {
+          text.push('*');
+        }
+        if let Some(time) = frame.time {
+          text.push_str(&format!("@{}", time));
+        }
+        text
+      })
+      .collect();
+
+    match state.hand {
+      Some(hand) => debug!("[{}] hand {}", frames.join(", "), hand),
+      None => debug!("[{}]", frames.join(", ")),
+    }
+  }
+
+  fn on_finish(&mut self, result: &SimulationResult) {
+    debug!("Hits: {} / {}", self.hits, self.requests);
+    if self.evictions > 0 {
+      debug!("Evictions: {}, mean age: {:.1} requests", self.evictions,
+        self.evicted_age as f64 / self.evictions as f64);
+    }
+    if result.prefetch_used + result.prefetch_wasted > 0 {
+      debug!("Prefetched: {}, used: {}", result.prefetch_used + result.prefetch_wasted,
+        result.prefetch_used);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use model::simulation::{CostModel, Simulation};
+  use super::*;
+
+  /// Keeps everything an observer is notified of
+  #[derive(Default)]
+  struct Recorder {
+    faults: Vec<bool>,
+    /// Faulted page and the frame it was loaded into
+    loads: Vec<(u64, usize)>,
+    /// Evicted page, frame and age
+    evictions: Vec<(u64, usize, u64)>,
+    tables: Vec<Vec<Option<u64>>>,
+    hit_rate: Option<f64>,
+  }
+
+  impl Observer for Recorder {
+    fn on_hit(&mut self, _request: &PageRequest) {
+      self.faults.push(false);
+    }
+
+    fn on_fault(&mut self, request: &PageRequest, frame: usize) {
+      self.faults.push(true);
+      self.loads.push((request.page, frame));
+    }
+
+    fn on_evict(&mut self, page: &u64, frame: usize, age: u64) {
+      self.evictions.push((*page, frame, age));
+    }
+
+    fn wants_table(&self) -> bool {
+      true
+    }
+
+    fn on_table(&mut self, state: &TableState) {
+      self.tables.push(state.frames.iter().map(|x| x.page).collect());
+    }
+
+    fn on_finish(&mut self, result: &SimulationResult) {
+      self.hit_rate = Some(result.hit_rate);
+    }
+  }
+
+  /// Runs a trace with fifo, notifying every observer
+  fn run(table_size: usize, trace: &[u64], observers: Vec<Box<dyn Observer>>)
+    -> SimulationResult {
+    let mut sim = Simulation::new(table_size, "fifo", None).unwrap();
+    for observer in observers {
+      sim.add_observer(observer);
+    }
+    sim.run(trace.iter().map(|&page| PageRequest { page, write: false })).unwrap();
+    sim.finish(table_size, &CostModel::default(), 0.0)
+  }
+
+  #[test]
+  fn run_notifies_every_observer() {
+    let trace = [7, 0, 1, 2, 0, 3, 0, 4, 2, 3, 0, 3, 2, 1, 2, 0, 1, 7, 0, 1];
+    let first = Arc::new(Mutex::new(Recorder::default()));
+    let second = Arc::new(Mutex::new(Recorder::default()));
+    let result = run(3, &trace, vec![Box::new(first.clone()), Box::new(second.clone())]);
+
+    let recorded = first.lock();
+    assert_eq!(recorded.faults.iter().filter(|&&x| x).count(), 15);
+    assert_eq!(recorded.hit_rate, Some(result.hit_rate));
+    // 7 loaded by the 1st request into frame 0 and replaced by the 4th
+    assert_eq!(recorded.evictions.len(), 12);
+    assert_eq!(recorded.evictions[0], (7, 0, 3));
+    assert_eq!(second.lock().evictions, recorded.evictions);
+  }
+
+  #[test]
+  fn shared_observer_forwards_every_callback() {
+    let shared = Arc::new(Mutex::new(Recorder::default()));
+    let observer: Box<dyn Observer> = Box::new(shared.clone());
+    assert!(observer.wants_table());
+    run(2, &[1, 2, 1, 1, 3, 2, 4], vec![observer]);
+
+    let recorded = shared.lock();
+    assert_eq!(recorded.faults, vec![true, true, false, false, true, false, true]);
+    assert_eq!(recorded.loads, vec![(1, 0), (2, 1), (3, 0), (4, 1)]);
+    // 1 loaded by the 1st request and replaced by the 5th, 2 loaded by the
+    // 2nd and replaced by the 7th, hits don't make pages younger
+    assert_eq!(recorded.evictions, vec![(1, 0, 4), (2, 1, 5)]);
+    assert_eq!(recorded.tables.len(), 7);
+    assert_eq!(recorded.tables[0], vec![Some(1), None]);
+    assert_eq!(recorded.tables[6], vec![Some(3), Some(4)]);
+    assert_eq!(recorded.hit_rate, Some(3.0 / 7.0));
+  }
+}
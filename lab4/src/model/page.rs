@@ -0,0 +1,82 @@
+use serde::Serialize;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// Identifier of a page, parsed from the first column of a trace line.
+/// Every value is a valid page, empty frames are `None`
+pub trait PageId: Clone + Debug + Display + Eq + Hash + Ord + FromStr + Serialize
+  + Send + Sync + 'static {
+  /// Gets the page as a number for prefetching, None if the identifier is
+  /// not a number that fits in a u64
+  fn to_number(&self) -> Option<u64>;
+
+  /// Creates a page from a number picked by a prefetcher, None if the
+  /// identifier is not a number
+  fn from_number(number: u64) -> Option<Self>;
+}
+
+impl PageId for u64 {
+  fn to_number(&self) -> Option<u64> {
+    Some(*self)
+  }
+
+  fn from_number(number: u64) -> Option<Self> {
+    Some(number)
+  }
+}
+
+impl PageId for u128 {
+  fn to_number(&self) -> Option<u64> {
+    if *self > u64::MAX as u128 {
+      return None;
+    }
+
+    Some(*self as u64)
+  }
+
+  fn from_number(number: u64) -> Option<Self> {
+    Some(number as u128)
+  }
+}
+
+/// Any identifier without whitespace or commas, ex: object keys or file
+/// names. Pages are not numbers so prefetchers can't be used
+impl PageId for String {
+  fn to_number(&self) -> Option<u64> {
+    None
+  }
+
+  fn from_number(_number: u64) -> Option<Self> {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use model::simulation::Simulation;
+  use std::sync::Arc;
+  use super::*;
+
+  /// Runs a whole trace, returns the number of page faults
+  fn faults<P: PageId>(algorithm: &str, trace: &[P]) -> u64 {
+    let mut sim = Simulation::new(3, algorithm, Some(Arc::new(trace.to_vec()))).unwrap();
+    for page in trace {
+      sim.request(page.clone()).unwrap();
+    }
+
+    sim.num_misses()
+  }
+
+  #[test]
+  fn string_and_u128_pages_match_numbers() {
+    let trace = [7, 0, 1, 2, 0, 3, 0, 4, 2, 3, 0, 3, 2, 1, 2, 0, 1, 7, 0, 1u64];
+    let strings: Vec<String> = trace.iter().map(|x| format!("page-{}", x)).collect();
+    let large: Vec<u128> = trace.iter().map(|&x| x as u128 + u64::MAX as u128).collect();
+    for algorithm in ["fifo", "lru", "second_chance", "optimal", "random", "nru"].iter() {
+      let expected = faults(algorithm, &trace);
+      assert_eq!(faults(algorithm, &strings), expected, "{}", algorithm);
+      assert_eq!(faults(algorithm, &large), expected, "{}", algorithm);
+    }
+  }
+}
@@ -0,0 +1,124 @@
+use std::convert::TryFrom;
+
+/// Picks pages to load ahead of time when a page fault occurs, layered on
+/// top of any page replacement algorithm
+#[derive(Clone, Debug)]
+pub enum Prefetcher {
+  /// Loads the next `degree` pages after the faulting page
+  Sequential {
+    degree: u64,
+  },
+  /// Loads the next `degree` pages following the distance between the last
+  /// requests, once the same distance was seen twice in a row
+  Stride {
+    degree: u64,
+    /// Previous page requested
+    last: Option<u64>,
+    /// Distance between the previous two requests, wide enough for any
+    /// distance between two pages
+    stride: Option<i128>,
+    /// If the last two strides were the same
+    confirmed: bool,
+  },
+  /// Loads the rest of the aligned cluster of `size` pages around the
+  /// faulting page, also known as fault-around
+  Cluster {
+    size: u64,
+  },
+}
+
+impl Prefetcher {
+  /// Creates a prefetcher by name, `degree` is the number of pages
+  /// loaded or the cluster size
+  pub fn new(kind: &str, degree: u64) -> Self {
+    match kind {
+      "sequential" => Prefetcher::Sequential { degree },
+      "stride" => Prefetcher::Stride {
+        degree,
+        last: None,
+        stride: None,
+        confirmed: false,
+      },
+      "cluster" => Prefetcher::Cluster { size: degree },
+      _ => unreachable!(),
+    }
+  }
+
+  /// Called on every page request, returns the pages to load if the
+  /// request caused a page fault
+  pub fn on_request(&mut self, page: u64, fault: bool) -> Vec<u64> {
+    match *self {
+      Prefetcher::Sequential { degree } => {
+        if !fault {
+          return Vec::new();
+        }
+
+        (1..=degree).filter_map(|i| page.checked_add(i)).collect()
+      },
+      Prefetcher::Stride { degree, ref mut last, ref mut stride, ref mut confirmed } => {
+        let new_stride = last.map(|x| page as i128 - x as i128);
+        *confirmed = new_stride.is_some() && new_stride == *stride && new_stride != Some(0);
+        *last = Some(page);
+        *stride = new_stride;
+
+        if !fault || !*confirmed {
+          return Vec::new();
+        }
+
+        // safe to unwrap, confirmed strides are set. Stops at the first
+        // page past either end of the page numbers
+        let stride = stride.unwrap();
+        (1..=degree as i128)
+          .map_while(|i| stride.checked_mul(i)
+            .and_then(|x| x.checked_add(page as i128))
+            .and_then(|x| u64::try_from(x).ok()))
+          .collect()
+      },
+      Prefetcher::Cluster { size } => {
+        if !fault {
+          return Vec::new();
+        }
+
+        let start = page - page % size;
+        (start..start.saturating_add(size))
+          .filter(|&x| x != page)
+          .collect()
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use model::simulation::Simulation;
+  use super::*;
+
+  /// Pages prefetched on a page fault after each request of the trace
+  fn stride_prefetches(trace: &[u64], degree: u64) -> Vec<u64> {
+    let mut prefetcher = Prefetcher::new("stride", degree);
+    trace.iter().flat_map(|&page| prefetcher.on_request(page, true)).collect()
+  }
+
+  #[test]
+  fn stride_stops_at_the_ends_of_the_page_numbers() {
+    assert_eq!(stride_prefetches(&[10, 20, 30], 2), vec![40, 50]);
+    assert_eq!(stride_prefetches(&[9, 6, 3], 4), vec![0]);
+
+    let max = u64::MAX;
+    assert_eq!(stride_prefetches(&[max - 4, max - 2, max], 4), Vec::<u64>::new());
+    assert_eq!(stride_prefetches(&[0, max, 0, max], 4), Vec::<u64>::new());
+    assert_eq!(stride_prefetches(&[max, 0, max, 0], 4), Vec::<u64>::new());
+  }
+
+  #[test]
+  fn prefetches_never_replace_the_requested_page() {
+    for algorithm in ["lru", "fifo", "second_chance", "random", "nru"].iter() {
+      let mut sim: Simulation = Simulation::new(2, algorithm, None).unwrap();
+      sim.set_prefetcher(Some(Prefetcher::new("sequential", 4)));
+      for _ in 0..5 {
+        sim.request(1).unwrap();
+      }
+      assert_eq!(sim.num_misses(), 1, "{}", algorithm);
+    }
+  }
+}
@@ -0,0 +1,210 @@
+use model::observer::Observer;
+use model::page::PageId;
+use model::simulation::PageRequest;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Thresholds used to classify the pages of a page report
+#[derive(Clone, Copy, Debug)]
+pub struct ReportThresholds {
+  /// Pages requested at least this many times the mean requests per page
+  /// are hot
+  pub hot_ratio: f64,
+  /// Pages requested at most this many times the mean requests per page
+  /// are cold
+  pub cold_ratio: f64,
+  /// Max number of requests between evicting a page and its next page
+  /// fault to count as a refault
+  pub thrash_distance: u64,
+  /// Pages with at least this many refaults are thrashing
+  pub thrash_refaults: u64,
+}
+
+/// Same as the defaults of the command line options
+impl Default for ReportThresholds {
+  fn default() -> Self {
+    ReportThresholds {
+      hot_ratio: 2.0,
+      cold_ratio: 0.5,
+      thrash_distance: 100,
+      thrash_refaults: 3,
+    }
+  }
+}
+
+/// Classification of a page, thrashing takes precedence over hot and cold
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageClass {
+  Hot,
+  Warm,
+  Cold,
+  Thrashing,
+}
+
+/// Counts of a single page while the simulation runs
+#[derive(Clone, Debug, Default)]
+struct PageStats {
+  accesses: u64,
+  faults: u64,
+  evictions: u64,
+  refaults: u64,
+  /// Sum of the requests the page stayed in the table for each time it was
+  /// loaded
+  resident: u64,
+  /// Number of times the page was loaded, including a load that lasts
+  /// until the end
+  loads: u64,
+  /// Request count when the page was loaded, None if it is not in the table
+  loaded_at: Option<u64>,
+  /// Request count when the page was last evicted
+  evicted_at: Option<u64>,
+}
+
+/// A row of the page report
+#[derive(Clone, Debug, Serialize)]
+pub struct PageSummary<P = u64> {
+  pub page: P,
+  pub accesses: u64,
+  pub faults: u64,
+  pub evictions: u64,
+  /// Page faults within the thrash distance of the page being evicted
+  pub refaults: u64,
+  /// Mean number of requests the page stayed in the table after being
+  /// loaded, pages still in the table at the end count until the end
+  pub mean_residency: f64,
+  pub class: PageClass,
+}
+
+/// Observer collecting per page accesses, faults, evictions and residency
+/// times of a simulation
+pub struct PageReport<P: PageId = u64> {
+  thresholds: ReportThresholds,
+  /// Number of page requests seen so far
+  requests: u64,
+  pages: HashMap<P, PageStats>,
+}
+
+impl<P: PageId> PageReport<P> {
+  pub fn new(thresholds: ReportThresholds) -> Self {
+    PageReport {
+      thresholds,
+      requests: 0,
+      pages: HashMap::new(),
+    }
+  }
+
+  /// Gets a row for every page that was requested or evicted, pages with
+  /// the most faults first
+  pub fn summaries(&self) -> Vec<PageSummary<P>> {
+    let accessed = self.pages.values().filter(|x| x.accesses > 0).count();
+    let mean_accesses = if accessed > 0 {
+      self.requests as f64 / accessed as f64
+    } else {
+      0.0
+    };
+
+    let mut summaries: Vec<PageSummary<P>> = self.pages
+      .iter()
+      .map(|(page, stats)| {
+        // a load still in the table lasts until the last request
+        let open = stats.loaded_at.map(|x| self.requests - x);
+        let loads = stats.loads + open.map_or(0, |_| 1);
+        let resident = stats.resident + open.unwrap_or(0);
+
+        let accesses = stats.accesses as f64;
+        let class = if stats.refaults >= self.thresholds.thrash_refaults {
+          PageClass::Thrashing
+        } else if accesses >= self.thresholds.hot_ratio * mean_accesses {
+          PageClass::Hot
+        } else if accesses <= self.thresholds.cold_ratio * mean_accesses {
+          PageClass::Cold
+        } else {
+          PageClass::Warm
+        };
+
+        PageSummary {
+          page: page.clone(),
+          accesses: stats.accesses,
+          faults: stats.faults,
+          evictions: stats.evictions,
+          refaults: stats.refaults,
+          mean_residency: if loads > 0 { resident as f64 / loads as f64 } else { 0.0 },
+          class,
+        }
+      })
+      .collect();
+
+    summaries.sort_by(|a, b| (Reverse(a.faults), &a.page).cmp(&(Reverse(b.faults), &b.page)));
+    summaries
+  }
+}
+
+impl<P: PageId> Observer<P> for PageReport<P> {
+  fn on_hit(&mut self, request: &PageRequest<P>) {
+    self.requests += 1;
+    self.pages.entry(request.page.clone()).or_default().accesses += 1;
+  }
+
+  fn on_fault(&mut self, request: &PageRequest<P>, _frame: usize) {
+    self.requests += 1;
+    let stats = self.pages.entry(request.page.clone()).or_default();
+    stats.accesses += 1;
+    stats.faults += 1;
+    if let Some(evicted_at) = stats.evicted_at {
+      if self.requests - evicted_at <= self.thresholds.thrash_distance {
+        stats.refaults += 1;
+      }
+    }
+    stats.loaded_at = Some(self.requests);
+  }
+
+  fn on_evict(&mut self, page: &P, _frame: usize, age: u64) {
+    let stats = self.pages.entry(page.clone()).or_default();
+    stats.evictions += 1;
+    stats.resident += age;
+    stats.loads += 1;
+    stats.loaded_at = None;
+    stats.evicted_at = Some(self.requests);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use model::simulation::Simulation;
+  use parking_lot::Mutex;
+  use std::sync::Arc;
+  use super::*;
+
+  #[test]
+  fn page_report_example() {
+    // 1 is evicted twice and faulted again right after, then hit 3 times
+    let trace = [1, 2, 3, 1, 2, 3, 1, 1, 1, 1u64];
+    let thresholds = ReportThresholds { hot_ratio: 1.5, cold_ratio: 0.7, ..Default::default() };
+    let report = Arc::new(Mutex::new(PageReport::new(thresholds)));
+    let thrashing = Arc::new(Mutex::new(PageReport::new(ReportThresholds {
+      thrash_refaults: 2,
+      ..thresholds
+    })));
+    let mut sim = Simulation::new(2, "lru", None).unwrap();
+    sim.add_observer(Box::new(report.clone()));
+    sim.add_observer(Box::new(thrashing.clone()));
+    sim.run(trace.iter().map(|&page| PageRequest { page, write: false })).unwrap();
+
+    let pages = report.lock().summaries();
+    let rows: Vec<_> = pages.iter()
+      .map(|x| (x.page, x.accesses, x.faults, x.evictions, x.refaults, x.class))
+      .collect();
+    assert_eq!(rows, vec![
+      (1, 6, 3, 2, 2, PageClass::Hot),
+      (2, 2, 2, 2, 1, PageClass::Cold),
+      (3, 2, 2, 1, 1, PageClass::Cold),
+    ]);
+    // 3 is still in the table, its last load lasts until the end
+    assert!((pages[0].mean_residency - 7.0 / 3.0).abs() < 1e-9);
+    assert_eq!(pages[1].mean_residency, 2.0);
+    assert_eq!(pages[2].mean_residency, 3.0);
+
+    assert_eq!(thrashing.lock().summaries()[0].class, PageClass::Thrashing);
+  }
+}
@@ -1,87 +1,503 @@
+use error::{Error, Result};
+use import::TraceFormat;
 use model::algorithms::*;
-use parking_lot::RwLock;
+use model::observer::Observer;
+use model::page::PageId;
+use model::prefetch::Prefetcher;
+use model::report::ReportThresholds;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 /// Options for a simulation
 pub struct SimulationOptions<'a> {
   pub input: Option<&'a str>,
-  pub table_size: usize,
-  pub to_table_size: Option<usize>,
-  pub algorithm: &'a str,
+  /// Table sizes to simulate for each algorithm
+  pub table_sizes: Vec<usize>,
+  pub algorithms: Vec<&'a str>,
   pub should_stdout: bool,
+  pub events: Option<&'a str>,
+  pub events_format: &'a str,
+  /// File to write per page statistics of each simulation to
+  pub page_report: Option<&'a str>,
+  /// Thresholds used to classify pages in the page report
+  pub report_thresholds: ReportThresholds,
+  pub progress: &'a str,
+  /// Number of threads to use, defaults to the number of CPUs
+  pub jobs: Option<usize>,
+  /// File to save completed simulations to as they finish
+  pub checkpoint: Option<&'a str>,
+  /// Skips simulations already saved in the checkpoint file
+  pub resume: bool,
+  pub cost: CostModel,
+  /// Prefetcher used for every simulation
+  pub prefetcher: Option<Prefetcher>,
+  /// Format of the trace, block traces are split into pages
+  pub format: TraceFormat,
+  /// File of pages pinned and unpinned before a number of page requests
+  pub pins: Option<&'a str>,
+  /// Type of page identifiers in the trace, one of `u64`, `u128` or `string`
+  pub page_ids: &'a str,
+  /// Parameters of the randomized algorithms, the seed is the first of
+  /// the repeated runs
+  pub params: AlgorithmParams,
+  /// Number of seeds each randomized algorithm is run with
+  pub repeat: usize,
+}
+
+/// A single page request from a trace
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageRequest<P = u64> {
+  pub page: P,
+  /// Writes make the page dirty, it has to be written back when evicted
+  pub write: bool,
+}
+
+/// A single line of a trace
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceEntry<P = u64> {
+  Request(PageRequest<P>),
+  /// Keeps a page from being replaced until it is unpinned
+  Pin(P),
+  Unpin(P),
+}
+
+/// A pin or unpin applied before the page request at `position`, starting
+/// at 0 for before the first request
+#[derive(Clone, Copy, Debug)]
+pub struct ScheduledPin<P = u64> {
+  pub position: u64,
+  pub entry: TraceEntry<P>,
+}
+
+/// Time in nanoseconds of the operations of a simulation, used to
+/// calculate the effective access time
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CostModel {
+  /// Time of every memory access
+  pub memory_ns: f64,
+  /// Time to load a page on a page fault
+  pub fault_ns: f64,
+  /// Time to write a dirty page back when it is evicted
+  pub write_back_ns: f64,
+}
+
+/// Same as the defaults of the command line options
+impl Default for CostModel {
+  fn default() -> Self {
+    CostModel {
+      memory_ns: 100.0,
+      fault_ns: 8_000_000.0,
+      write_back_ns: 8_000_000.0,
+    }
+  }
+}
+
+/// Result of a single simulation
+#[derive(Clone, Debug)]
+pub struct SimulationResult {
+  pub table_size: usize,
+  pub hit_rate: f64,
+  /// Time spent running the simulation in seconds
+  pub elapsed_secs: f64,
+  /// Average simulated time of a page request in nanoseconds
+  pub effective_access_ns: f64,
+  /// Simulated time of all page requests in nanoseconds
+  pub total_ns: f64,
+  /// Page faults caused by page requests, not counting prefetched pages
+  pub demand_faults: u64,
+  /// Prefetched pages that were requested before being evicted
+  pub prefetch_used: u64,
+  /// Prefetched pages that were never requested
+  pub prefetch_wasted: u64,
+  /// Number of seeds the results are averaged over, 1 for a single run
+  pub runs: usize,
+  /// Sample standard deviation of the hit rate over the runs, 0 for a
+  /// single run
+  pub hit_rate_stddev: f64,
+}
+
+impl SimulationResult {
+  /// Averages the results of the same table size run with different
+  /// seeds. `results` must not be empty
+  pub fn mean(results: &[SimulationResult]) -> SimulationResult {
+    let runs = results.len();
+    let mean = |f: fn(&SimulationResult) -> f64| {
+      results.iter().map(f).sum::<f64>() / runs as f64
+    };
+
+    let hit_rate = mean(|x| x.hit_rate);
+    let hit_rate_stddev = if runs > 1 {
+      let variance = results.iter()
+        .map(|x| (x.hit_rate - hit_rate).powi(2))
+        .sum::<f64>() / (runs - 1) as f64;
+      variance.sqrt()
+    } else {
+      0.0
+    };
+
+    SimulationResult {
+      table_size: results[0].table_size,
+      hit_rate,
+      elapsed_secs: mean(|x| x.elapsed_secs),
+      effective_access_ns: mean(|x| x.effective_access_ns),
+      total_ns: mean(|x| x.total_ns),
+      demand_faults: mean(|x| x.demand_faults as f64).round() as u64,
+      prefetch_used: mean(|x| x.prefetch_used as f64).round() as u64,
+      prefetch_wasted: mean(|x| x.prefetch_wasted as f64).round() as u64,
+      runs,
+      hit_rate_stddev,
+    }
+  }
+}
+
+/// Observers of a simulation, a cloned simulation starts without any
+struct Observers<P: PageId>(Vec<Box<dyn Observer<P>>>);
+
+impl<P: PageId> Clone for Observers<P> {
+  fn clone(&self) -> Self {
+    Observers(Vec::new())
+  }
 }
 
 /// A holder for simulation data
-pub struct Simulation {
-  algorithm: AlgorithmType,
+#[derive(Clone)]
+pub struct Simulation<P: PageId = u64> {
+  algorithm: AlgorithmType<P>,
   num_requests: u64,
   num_misses: u64,
+  /// Number of dirty pages written back when evicted
+  num_write_backs: u64,
+  /// Pages in the table that were written since they were loaded
+  dirty: HashSet<P>,
+  prefetcher: Option<Prefetcher>,
+  /// Prefetched pages in the table that were not requested yet
+  prefetched: HashSet<P>,
+  /// Number of pages loaded by the prefetcher
+  num_prefetched: u64,
+  /// Number of prefetched pages that were requested
+  num_prefetch_used: u64,
+  /// Pages that are never replaced
+  pinned: HashSet<P>,
+  /// Pins applied at a position in the trace, sorted by position
+  pins: Arc<Vec<ScheduledPin<P>>>,
+  /// Index of the next pin to apply
+  next_pin: usize,
+  observers: Observers<P>,
+  /// Number of page requests made when each page in the table was loaded,
+  /// only kept when there are observers
+  loaded: HashMap<P, u64>,
 }
 
-impl Simulation {
-  /// Creates a new simulation with a specified table size and algorithm type
-  pub fn new(table_size: usize, algorithm: &str,
-    page_requests: Option<Arc<RwLock<Vec<String>>>>) -> Self {
+impl<P: PageId> Simulation<P> {
+  /// Creates a new simulation with a specified table size and algorithm
+  /// type, optimal needs every page request of the trace
+  pub fn new(table_size: usize, algorithm: &str, page_requests: Option<Arc<Vec<P>>>)
+    -> Result<Self> {
+    Simulation::with_params(table_size, algorithm, &AlgorithmParams::default(), page_requests)
+  }
+
+  /// Creates a new simulation, randomized algorithms are seeded from the
+  /// parameters. Errors on unknown algorithms and on optimal without the
+  /// page requests
+  pub fn with_params(table_size: usize, algorithm: &str, params: &AlgorithmParams,
+    page_requests: Option<Arc<Vec<P>>>) -> Result<Self> {
     let algorithm = match algorithm {
       "fifo" => AlgorithmType::Fifo(Fifo::new(table_size)),
       "lru" => AlgorithmType::Lru(Lru::new(table_size)),
-      "optimal" => AlgorithmType::Optimal(Optimal::new(table_size, page_requests)),
+      "nru" => AlgorithmType::Nru(Nru::new(table_size, params.seed, params.nru_interval)),
+      "optimal" => AlgorithmType::Optimal(Optimal::new(table_size, page_requests)?),
+      "random" => AlgorithmType::Random(Random::new(table_size, params.seed)),
       "second_chance" | "sc" => AlgorithmType::SecondChance(SecondChance::new(table_size)),
-      _ => unreachable!(),
+      _ => return Err(Error::Algorithm(format!("Unknown algorithm {}", algorithm))),
     };
 
-    Simulation {
+    Ok(Simulation {
       algorithm,
       num_requests: 0,
       num_misses: 0,
+      num_write_backs: 0,
+      dirty: HashSet::new(),
+      prefetcher: None,
+      prefetched: HashSet::new(),
+      num_prefetched: 0,
+      num_prefetch_used: 0,
+      pinned: HashSet::new(),
+      pins: Arc::new(Vec::new()),
+      next_pin: 0,
+      observers: Observers(Vec::new()),
+      loaded: HashMap::new(),
+    })
+  }
+
+  /// Sets pins to apply at positions in the trace, sorted by position
+  pub fn set_pins(&mut self, pins: Arc<Vec<ScheduledPin<P>>>) {
+    self.pins = pins;
+    self.next_pin = 0;
+  }
+
+  /// Keeps a page from being replaced, the page does not have to be in
+  /// the table yet
+  pub fn pin(&mut self, page: P) {
+    trace!("Pinned page {}", page);
+    self.pinned.insert(page);
+  }
+
+  /// Allows a pinned page to be replaced again
+  pub fn unpin(&mut self, page: P) {
+    trace!("Unpinned page {}", page);
+    self.pinned.remove(&page);
+  }
+
+  /// Sets the prefetcher used to load pages ahead of page requests
+  pub fn set_prefetcher(&mut self, prefetcher: Option<Prefetcher>) {
+    self.prefetcher = prefetcher;
+  }
+
+  /// Adds an observer notified of every page request and the results,
+  /// observers are notified in the order they were added
+  pub fn add_observer(&mut self, observer: Box<dyn Observer<P>>) {
+    self.observers.0.push(observer);
+  }
+
+  /// Runs every page request in order, stops at the first error
+  pub fn run<I: IntoIterator<Item = PageRequest<P>>>(&mut self, requests: I) -> Result<()> {
+    for request in requests {
+      self.step(request)?;
     }
+
+    Ok(())
   }
 
-  /// Parses a string to a u64 and validates if > 0
-  pub fn parse_line(&self, line: &str) -> Option<u64> {
-    line.parse::<u64>()
-      .ok()
-      .and_then(|num| {
-        if num <= 0 {
-          None
-        } else {
-          Some(num)
-        }
-      })
+  /// Runs a parsed trace line, returns the outcome if it is a page request
+  pub fn run_entry(&mut self, entry: TraceEntry<P>) -> Result<Option<Outcome<P>>> {
+    match entry {
+      TraceEntry::Request(request) => self.step(request).map(Some),
+      TraceEntry::Pin(page) => {
+        self.pin(page);
+        Ok(None)
+      },
+      TraceEntry::Unpin(page) => {
+        self.unpin(page);
+        Ok(None)
+      },
+    }
   }
 
-  /// Runs a single page request on the page table
-  pub fn page_request(&mut self, page_request: &str, should_stdout: bool) {
-    let page_request = match self.parse_line(page_request) {
-      Some(req) => req,
-      None => return,
-    };
+  /// Runs a single page read on the page table
+  pub fn request(&mut self, page_request: P) -> Result<Outcome<P>> {
+    self.step(PageRequest { page: page_request, write: false })
+  }
+
+  /// Runs a single page request on the page table, keeping track of dirty
+  /// pages. Errors if the page is not in the table and every frame is
+  /// pinned
+  pub fn step(&mut self, request: PageRequest<P>) -> Result<Outcome<P>> {
+    // pins scheduled before this request
+    while self.next_pin < self.pins.len()
+      && self.pins[self.next_pin].position <= self.num_requests {
+      let pin = self.pins[self.next_pin].entry.clone();
+      self.next_pin += 1;
+      self.run_entry(pin)?;
+    }
 
     self.num_requests += 1;
 
     // run corresponding page replacement algorithms
-    let res = match self.algorithm {
-      AlgorithmType::Fifo(ref mut x) => x.handle_page_request(page_request, should_stdout),
-      AlgorithmType::Lru(ref mut x) => x.handle_page_request(page_request, should_stdout),
-      AlgorithmType::Optimal(ref mut x) => x.handle_page_request(page_request, should_stdout),
-      AlgorithmType::SecondChance(ref mut x) => x.handle_page_request(page_request, should_stdout),
-    };
-    
+    let outcome = self.algorithm.handle_page_request(request.page.clone(), &self.pinned)?;
+
     // check if resulted in page fault
-    if res {
+    if outcome.is_fault() {
       self.num_misses += 1;
     }
+
+    self.evict(&outcome);
+
+    if !self.observers.0.is_empty() {
+      match outcome {
+        Outcome::Hit => {
+          for observer in self.observers.0.iter_mut() {
+            observer.on_hit(&request);
+          }
+        },
+        Outcome::Fault { frame, .. } => {
+          self.loaded.insert(request.page.clone(), self.num_requests);
+          for observer in self.observers.0.iter_mut() {
+            observer.on_fault(&request, frame);
+          }
+        },
+      }
+    }
+
+    let PageRequest { page: page_request, write } = request;
+    if write {
+      self.algorithm.mark_modified(&page_request);
+      self.dirty.insert(page_request.clone());
+    }
+
+    // first request of a prefetched page
+    if self.prefetched.remove(&page_request) {
+      self.num_prefetch_used += 1;
+    }
+
+    // prefetchers only work on page numbers
+    let candidates = match (self.prefetcher.as_mut(), page_request.to_number()) {
+      (Some(prefetcher), Some(number)) => prefetcher.on_request(number, outcome.is_fault()),
+      _ => Vec::new(),
+    };
+    // the requested page is pinned while prefetching, so prefetched pages
+    // never replace it
+    if !candidates.is_empty() {
+      let pinned = self.pinned.insert(page_request.clone());
+      for page in candidates.into_iter().filter_map(P::from_number) {
+        self.prefetch(page);
+      }
+      if pinned {
+        self.pinned.remove(&page_request);
+      }
+    }
+
+    if self.observers.0.iter().any(|x| x.wants_table()) {
+      let state = self.algorithm.state();
+      for observer in self.observers.0.iter_mut() {
+        observer.on_table(&state);
+      }
+    }
+
+    Ok(outcome)
+  }
+
+  /// Loads a page without counting it as a page request, nothing is loaded
+  /// if every frame is pinned
+  fn prefetch(&mut self, page: P) {
+    if self.algorithm.contains(&page) {
+      return;
+    }
+
+    let outcome = match self.algorithm.handle_page_request(page.clone(), &self.pinned) {
+      Ok(outcome) => outcome,
+      Err(_) => return,
+    };
+    trace!("Prefetched page {}", page);
+    self.evict(&outcome);
+    if !self.observers.0.is_empty() {
+      self.loaded.insert(page.clone(), self.num_requests);
+    }
+    self.prefetched.insert(page);
+    self.num_prefetched += 1;
+  }
+
+  /// Writes back the page replaced by a page fault if it is dirty
+  fn evict(&mut self, outcome: &Outcome<P>) {
+    if let Outcome::Fault { evicted: Some(ref evicted), frame } = *outcome {
+      if self.dirty.remove(evicted) {
+        self.num_write_backs += 1;
+      }
+
+      self.prefetched.remove(evicted);
+
+      if !self.observers.0.is_empty() {
+        let age = self.loaded.remove(evicted).map_or(0, |x| self.num_requests - x);
+        for observer in self.observers.0.iter_mut() {
+          observer.on_evict(evicted, frame, age);
+        }
+      }
+    }
+  }
+
+  /// Removes a page from the page table, used to keep a level in sync with
+  /// pages evicted from another level. Returns if the page was in the table
+  pub fn invalidate(&mut self, page: &P) -> bool {
+    self.dirty.remove(page);
+    self.prefetched.remove(page);
+    self.loaded.remove(page);
+    self.algorithm.invalidate(page)
+  }
+
+  /// Gets the number of valid page requests made
+  pub fn num_requests(&self) -> u64 {
+    self.num_requests
+  }
+
+  /// Gets the number of page requests that caused a page fault
+  pub fn num_misses(&self) -> u64 {
+    self.num_misses
+  }
+
+  /// Gets the number of dirty pages written back when evicted
+  pub fn num_write_backs(&self) -> u64 {
+    self.num_write_backs
+  }
+
+  /// Gets a snapshot of the page table contents
+  pub fn table_state(&self) -> TableState<P> {
+    self.algorithm.state()
   }
 
   /// Calculates the hit rate from number of hits / misses
-  pub fn get_hit_rate(&self, should_stdout: bool) -> f64 {
+  pub fn get_hit_rate(&self) -> f64 {
     let num_hits = self.num_requests - self.num_misses;
-    let hit_rate = num_hits as f64 / self.num_requests as f64;
-    debug!("Hits: {} / {}", num_hits, self.num_requests);
-    if should_stdout {
-      println!("Hit rate: {:.5}",  hit_rate);
+    num_hits as f64 / self.num_requests as f64
+  }
+
+  /// Calculates the simulated time of all requests in nanoseconds
+  pub fn simulated_ns(&self, cost: &CostModel) -> f64 {
+    self.num_requests as f64 * cost.memory_ns
+      + self.num_misses as f64 * cost.fault_ns
+      + self.num_write_backs as f64 * cost.write_back_ns
+  }
+
+  /// Collects the results of a finished simulation
+  pub fn result(&self, table_size: usize, cost: &CostModel, elapsed_secs: f64)
+    -> SimulationResult {
+    let hit_rate = self.get_hit_rate();
+    let total_ns = self.simulated_ns(cost);
+    let effective_access_ns = if self.num_requests > 0 {
+      total_ns / self.num_requests as f64
+    } else {
+      0.0
+    };
+
+    SimulationResult {
+      table_size,
+      hit_rate,
+      elapsed_secs,
+      effective_access_ns,
+      total_ns,
+      demand_faults: self.num_misses,
+      prefetch_used: self.num_prefetch_used,
+      prefetch_wasted: self.num_prefetched - self.num_prefetch_used,
+      runs: 1,
+      hit_rate_stddev: 0.0,
+    }
+  }
+
+  /// Collects the results of a finished simulation and passes them to the
+  /// observers
+  pub fn finish(&mut self, table_size: usize, cost: &CostModel, elapsed_secs: f64)
+    -> SimulationResult {
+    let result = self.result(table_size, cost, elapsed_secs);
+    for observer in self.observers.0.iter_mut() {
+      observer.on_finish(&result);
     }
 
-    hit_rate
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn all_pinned_frames_error() {
+    for algorithm in ["fifo", "lru", "second_chance", "optimal", "random", "nru"].iter() {
+      let mut sim = Simulation::new(2, algorithm, Some(Arc::new(vec![1u64, 2, 3]))).unwrap();
+      sim.pin(1);
+      sim.pin(2);
+      sim.request(1).unwrap();
+      sim.request(2).unwrap();
+      assert!(sim.request(3).is_err(), "{}", algorithm);
+    }
   }
 }
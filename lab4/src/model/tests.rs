@@ -0,0 +1,198 @@
+//! Invariants of every page replacement algorithm, checked on random traces
+//! and against small textbook examples
+
+use model::algorithms::Outcome;
+use model::page::PageId;
+use model::simulation::Simulation;
+use proptest::prelude::*;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+const ALGORITHMS: [&str; 6] = ["fifo", "lru", "second_chance", "optimal", "random", "nru"];
+
+/// Reference string used by most operating systems textbooks
+const TEXTBOOK_TRACE: [u64; 20] = [7, 0, 1, 2, 0, 3, 0, 4, 2, 3, 0, 3, 2, 1, 2, 0, 1, 7, 0, 1];
+
+/// Reference string showing Belady's anomaly for FIFO
+const BELADY_TRACE: [u64; 12] = [1, 2, 3, 4, 1, 2, 5, 1, 2, 3, 4, 5];
+
+/// Creates a simulation, optimal looks ahead in the trace
+fn simulation<P: PageId>(algorithm: &str, table_size: usize, trace: &[P]) -> Simulation<P> {
+  Simulation::new(table_size, algorithm, Some(Arc::new(trace.to_vec()))).unwrap()
+}
+
+/// Runs a whole trace, returns the number of page faults
+fn faults<P: PageId>(algorithm: &str, table_size: usize, trace: &[P]) -> u64 {
+  let mut sim = simulation(algorithm, table_size, trace);
+  for page in trace {
+    sim.request(page.clone()).unwrap();
+  }
+
+  sim.num_misses()
+}
+
+/// Pages currently in the page table
+fn resident(sim: &Simulation) -> HashSet<u64> {
+  sim.table_state()
+    .frames
+    .iter()
+    .filter_map(|x| x.page)
+    .collect()
+}
+
+/// Simpler implementation of an algorithm, returns if each request faulted
+type ReferenceModel = fn(usize, &[u64]) -> Vec<bool>;
+
+/// FIFO kept as a queue, evicting the page loaded first
+fn reference_fifo(table_size: usize, trace: &[u64]) -> Vec<bool> {
+  let mut queue = VecDeque::new();
+  trace.iter()
+    .map(|page| {
+      if queue.contains(page) {
+        return false;
+      }
+
+      if queue.len() == table_size {
+        queue.pop_front();
+      }
+      queue.push_back(*page);
+      true
+    })
+    .collect()
+}
+
+/// LRU kept as a list ordered by last use, evicting the front
+fn reference_lru(table_size: usize, trace: &[u64]) -> Vec<bool> {
+  let mut list = VecDeque::new();
+  trace.iter()
+    .map(|page| {
+      let fault = match list.iter().position(|x| x == page) {
+        Some(index) => {
+          list.remove(index);
+          false
+        },
+        None => {
+          if list.len() == table_size {
+            list.pop_front();
+          }
+          true
+        },
+      };
+
+      list.push_back(*page);
+      fault
+    })
+    .collect()
+}
+
+fn trace_strategy() -> impl Strategy<Value = Vec<u64>> {
+  prop::collection::vec(0u64..12, 0..200)
+}
+
+proptest! {
+  #[test]
+  fn resident_set_fits_and_hits_iff_resident(trace in trace_strategy(), table_size in 1usize..=8) {
+    for algorithm in ALGORITHMS.iter() {
+      let mut sim = simulation(algorithm, table_size, &trace);
+      for &page in &trace {
+        let before = resident(&sim);
+        let outcome = sim.request(page).unwrap();
+        let after = resident(&sim);
+
+        prop_assert_eq!(outcome == Outcome::Hit, before.contains(&page), "{}", algorithm);
+        prop_assert!(after.contains(&page), "{} did not load page {}", algorithm, page);
+        prop_assert!(after.len() <= table_size, "{} holds {} pages", algorithm, after.len());
+
+        match outcome {
+          Outcome::Hit => prop_assert_eq!(&before, &after, "{}", algorithm),
+          Outcome::Fault { evicted: Some(evicted), frame } => {
+            prop_assert_eq!(before.len(), table_size, "{} evicted with a free frame", algorithm);
+            prop_assert!(before.contains(&evicted) && !after.contains(&evicted), "{}", algorithm);
+            prop_assert_eq!(sim.table_state().frames[frame].page, Some(page), "{}", algorithm);
+          },
+          Outcome::Fault { evicted: None, frame } => {
+            prop_assert_eq!(after.len(), before.len() + 1, "{}", algorithm);
+            prop_assert_eq!(sim.table_state().frames[frame].page, Some(page), "{}", algorithm);
+          },
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn cold_start_faults_on_every_distinct_page(trace in trace_strategy(), table_size in 1usize..=8) {
+    let distinct = trace.iter().collect::<HashSet<_>>().len() as u64;
+    for algorithm in ALGORITHMS.iter() {
+      prop_assert!(faults(algorithm, table_size, &trace) >= distinct, "{}", algorithm);
+    }
+  }
+
+  #[test]
+  fn lru_and_optimal_obey_inclusion(trace in trace_strategy()) {
+    for algorithm in ["lru", "optimal"].iter() {
+      let mut sims: Vec<Simulation> = (1..=8)
+        .map(|size| simulation(algorithm, size, &trace))
+        .collect();
+
+      for &page in &trace {
+        for sim in sims.iter_mut() {
+          sim.request(page).unwrap();
+        }
+
+        // pages held with k frames are also held with k + 1 frames
+        for pair in sims.windows(2) {
+          prop_assert!(resident(&pair[0]).is_subset(&resident(&pair[1])), "{}", algorithm);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn optimal_never_faults_more(trace in trace_strategy(), table_size in 1usize..=8) {
+    let optimal = faults("optimal", table_size, &trace);
+    for algorithm in ALGORITHMS.iter() {
+      prop_assert!(optimal <= faults(algorithm, table_size, &trace), "{}", algorithm);
+    }
+  }
+
+  #[test]
+  fn fifo_and_lru_match_reference_models(trace in trace_strategy(), table_size in 1usize..=8) {
+    let models: [(&str, ReferenceModel); 2] = [("fifo", reference_fifo), ("lru", reference_lru)];
+    for &(algorithm, model) in models.iter() {
+      let mut sim = simulation(algorithm, table_size, &trace);
+      let outcomes: Vec<bool> = trace.iter()
+        .map(|&page| sim.request(page).unwrap().is_fault())
+        .collect();
+      prop_assert_eq!(outcomes, model(table_size, &trace), "{}", algorithm);
+    }
+  }
+
+  #[test]
+  fn randomized_algorithms_repeat_with_the_same_seed(trace in trace_strategy(),
+    table_size in 1usize..=8) {
+    for algorithm in ["random", "nru"].iter() {
+      let mut a = simulation(algorithm, table_size, &trace);
+      let mut b = simulation(algorithm, table_size, &trace);
+      for &page in &trace {
+        prop_assert_eq!(a.request(page).unwrap(), b.request(page).unwrap());
+      }
+    }
+  }
+}
+
+#[test]
+fn textbook_examples() {
+  assert_eq!(faults("fifo", 3, &TEXTBOOK_TRACE), 15);
+  assert_eq!(faults("lru", 3, &TEXTBOOK_TRACE), 12);
+  assert_eq!(faults("optimal", 3, &TEXTBOOK_TRACE), 9);
+  // new pages start unreferenced, the hand clears pages it passes over
+  assert_eq!(faults("second_chance", 3, &TEXTBOOK_TRACE), 11);
+}
+
+#[test]
+fn fifo_shows_beladys_anomaly() {
+  assert_eq!(faults("fifo", 3, &BELADY_TRACE), 9);
+  assert_eq!(faults("fifo", 4, &BELADY_TRACE), 10);
+  assert_eq!(faults("lru", 3, &BELADY_TRACE), 10);
+  assert_eq!(faults("lru", 4, &BELADY_TRACE), 8);
+}
@@ -0,0 +1,522 @@
+use analyze::{self, Fenwick};
+use csv::Writer;
+use error::Result;
+use serde_json;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use util;
+
+/// Number of independent subsamples the sampled pages are split into to
+/// estimate the error of the miss ratios
+const ERROR_GROUPS: usize = 8;
+
+/// Hash value range, pages with a hash below `rate * HASH_RANGE` are sampled
+const HASH_RANGE: u128 = 1 << 64;
+
+/// Positions kept in a sampled stack beyond twice its pages before older
+/// positions are dropped, avoids compacting small stacks on every request
+const MIN_POSITIONS: usize = 1024;
+
+/// Options for estimating miss ratio curves
+pub struct MrcOptions<'a> {
+  pub input: Option<&'a str>,
+  pub output: Option<&'a str>,
+  pub format: &'a str,
+  /// Table sizes to estimate the miss ratio of
+  pub table_sizes: Vec<usize>,
+  pub sampling: Sampling,
+  /// Also calculates the exact miss ratios, keeping the whole trace in
+  /// memory
+  pub validate: bool,
+}
+
+/// How pages are sampled
+#[derive(Clone, Copy, Debug)]
+pub enum Sampling {
+  /// Samples a fixed fraction of the pages
+  Rate(f64),
+  /// Samples at most this many pages, lowering the rate as new pages are
+  /// requested
+  FixedSize(usize),
+}
+
+/// Spreads page numbers evenly over all hash values (splitmix64), so
+/// sampling by hash picks pages independent of their numbers
+fn page_hash(page: u64) -> u64 {
+  let mut x = page.wrapping_add(0x9e37_79b9_7f4a_7c15);
+  x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  x ^ (x >> 31)
+}
+
+/// LRU stack of the sampled pages, counting requests by their reuse
+/// distance scaled up to the whole trace
+struct SampledStack {
+  /// 1 at positions that are currently the latest request of a sampled
+  /// page. Positions of older requests are dropped by compacting
+  latest: Fenwick,
+  last_seen: HashMap<u64, usize>,
+  /// Estimated number of requests by scaled reuse distance
+  distances: BTreeMap<u64, f64>,
+  /// Estimated number of first requests of a page
+  cold: f64,
+}
+
+impl SampledStack {
+  fn new() -> Self {
+    SampledStack {
+      latest: Fenwick::new(0),
+      last_seen: HashMap::new(),
+      distances: BTreeMap::new(),
+      cold: 0.0,
+    }
+  }
+
+  /// Adds a request of a page sampled at `rate`, it stands for `1 / rate`
+  /// requests of the whole trace
+  fn access(&mut self, page: u64, rate: f64) {
+    if self.latest.len() >= 2 * self.last_seen.len() + MIN_POSITIONS {
+      self.compact();
+    }
+
+    let position = self.latest.len();
+    match self.last_seen.insert(page, position) {
+      Some(prev) => {
+        let distance = self.latest.sum(position) - self.latest.sum(prev + 1);
+        self.latest.add(prev, -1);
+        *self.distances.entry((distance as f64 / rate) as u64).or_insert(0.0) += 1.0 / rate;
+      },
+      None => self.cold += 1.0 / rate,
+    }
+
+    self.latest.push(1);
+  }
+
+  /// Renumbers the latest requests of the sampled pages in order, so the
+  /// tree grows with the number of sampled pages instead of the trace
+  fn compact(&mut self) {
+    let mut positions: Vec<(usize, u64)> = self.last_seen
+      .iter()
+      .map(|(&page, &position)| (position, page))
+      .collect();
+    positions.sort_unstable();
+
+    self.latest = Fenwick::new(0);
+    for (position, (_, page)) in positions.into_iter().enumerate() {
+      self.last_seen.insert(page, position);
+      self.latest.push(1);
+    }
+  }
+
+  /// Stops tracking a page that is no longer sampled
+  fn remove(&mut self, page: u64) {
+    if let Some(prev) = self.last_seen.remove(&page) {
+      self.latest.add(prev, -1);
+    }
+  }
+
+  /// Estimates the miss ratio of LRU with `table_size` frames. Requests
+  /// with a reuse distance of at least the table size are misses. Like
+  /// SHARDS_adj, the misses are divided by the actual number of requests
+  /// instead of the estimated number, which corrects for sampling more or
+  /// fewer requests than expected
+  fn miss_ratio(&self, table_size: usize, requests: u64) -> f64 {
+    if requests == 0 {
+      return 0.0;
+    }
+
+    let misses = self.cold + self.distances
+      .range(table_size as u64..)
+      .map(|x| x.1)
+      .sum::<f64>();
+    (misses / requests as f64).min(1.0)
+  }
+}
+
+/// Estimates LRU miss ratio curves from a spatially hashed sample of the
+/// pages (SHARDS, Waldspurger et al. 2015). Every request of a sampled page
+/// is kept, so reuse distances within the sample scaled by the sampling
+/// rate estimate the reuse distances of the whole trace. Table sizes below
+/// about `10 / rate` are too small for the sample to tell apart
+pub struct Shards {
+  sampling: Sampling,
+  /// Pages with a hash below the threshold are sampled
+  threshold: u128,
+  stack: SampledStack,
+  /// The same sample split by hash, each is a sample at `rate / ERROR_GROUPS`
+  groups: Vec<SampledStack>,
+  /// Hashes of the sampled pages, only kept for a fixed size sample
+  sampled: BTreeSet<(u64, u64)>,
+  requests: u64,
+  sampled_requests: u64,
+}
+
+impl Shards {
+  pub fn new(sampling: Sampling) -> Self {
+    let threshold = match sampling {
+      Sampling::Rate(rate) => (rate * HASH_RANGE as f64) as u128,
+      Sampling::FixedSize(_) => HASH_RANGE,
+    };
+
+    Shards {
+      sampling,
+      threshold,
+      stack: SampledStack::new(),
+      groups: (0..ERROR_GROUPS).map(|_| SampledStack::new()).collect(),
+      sampled: BTreeSet::new(),
+      requests: 0,
+      sampled_requests: 0,
+    }
+  }
+
+  /// Current sampling rate, only lowered by a fixed size sample
+  pub fn rate(&self) -> f64 {
+    self.threshold as f64 / HASH_RANGE as f64
+  }
+
+  /// Number of requests seen
+  pub fn requests(&self) -> u64 {
+    self.requests
+  }
+
+  /// Number of requests of sampled pages
+  pub fn sampled_requests(&self) -> u64 {
+    self.sampled_requests
+  }
+
+  /// Number of pages currently sampled
+  pub fn sampled_pages(&self) -> usize {
+    self.stack.last_seen.len()
+  }
+
+  /// Adds a page request to the sample if the page is sampled
+  pub fn access(&mut self, page: u64) {
+    self.requests += 1;
+    let hash = page_hash(page);
+    if hash as u128 >= self.threshold {
+      return;
+    }
+
+    if let Sampling::FixedSize(max_pages) = self.sampling {
+      if self.sampled.insert((hash, page)) && self.sampled.len() > max_pages {
+        // drop the page with the largest hash, lowering the rate so it
+        // and every page above it are no longer sampled
+        let (max_hash, max_page) = *self.sampled.iter().next_back().unwrap();
+        self.sampled.remove(&(max_hash, max_page));
+        self.threshold = max_hash as u128;
+        self.stack.remove(max_page);
+        self.groups[max_hash as usize % ERROR_GROUPS].remove(max_page);
+
+        if max_page == page {
+          return;
+        }
+      }
+    }
+
+    let rate = self.rate();
+    self.sampled_requests += 1;
+    self.stack.access(page, rate);
+    self.groups[hash as usize % ERROR_GROUPS].access(page, rate / ERROR_GROUPS as f64);
+  }
+
+  /// Estimates the miss ratio of LRU with `table_size` frames, NaN if no
+  /// requests were sampled
+  pub fn miss_ratio(&self, table_size: usize) -> f64 {
+    if self.sampled_requests == 0 {
+      return f64::NAN;
+    }
+
+    self.stack.miss_ratio(table_size, self.requests)
+  }
+
+  /// Estimates the standard error of a miss ratio from the spread of the
+  /// miss ratios of the independent subsamples. 0 if every page was sampled
+  pub fn std_error(&self, table_size: usize) -> f64 {
+    if self.threshold >= HASH_RANGE {
+      return 0.0;
+    }
+
+    let estimates: Vec<f64> = self.groups
+      .iter()
+      .map(|x| x.miss_ratio(table_size, self.requests))
+      .collect();
+    let mean = estimates.iter().sum::<f64>() / ERROR_GROUPS as f64;
+    let variance = estimates.iter()
+      .map(|x| (x - mean).powi(2))
+      .sum::<f64>() / (ERROR_GROUPS - 1) as f64;
+
+    // the full sample has ERROR_GROUPS times the pages of a subsample
+    (variance / ERROR_GROUPS as f64).sqrt()
+  }
+}
+
+/// Calculates the exact LRU miss ratio of every table size from the reuse
+/// distances of the whole trace, the same as simulating LRU for each size
+pub fn exact_miss_ratios(requests: &[u64], table_sizes: &[usize]) -> Vec<f64> {
+  let mut distances: Vec<u64> = analyze::reuse_distances(requests)
+    .into_iter()
+    .map(|x| x.unwrap_or(u64::MAX))
+    .collect();
+  distances.sort_unstable();
+
+  table_sizes
+    .iter()
+    .map(|&size| {
+      let hits = distances.partition_point(|&x| x < size as u64);
+      (requests.len() - hits) as f64 / requests.len().max(1) as f64
+    })
+    .collect()
+}
+
+/// Estimated miss ratio of a single table size
+#[derive(Debug, Serialize)]
+pub struct MrcPoint {
+  pub table_size: usize,
+  pub miss_ratio: f64,
+  /// Estimated standard error of the miss ratio
+  pub std_error: f64,
+  /// Exact miss ratio, only calculated when validating
+  pub exact_miss_ratio: Option<f64>,
+  /// Difference between the estimated and exact miss ratio
+  pub error: Option<f64>,
+}
+
+/// Estimated miss ratio curve with the sample it was estimated from
+#[derive(Debug, Serialize)]
+pub struct MissRatioCurve {
+  pub requests: u64,
+  pub sampled_requests: u64,
+  pub sampled_pages: usize,
+  /// Final sampling rate
+  pub rate: f64,
+  /// Mean absolute error against the exact miss ratios, only when validating
+  pub mean_abs_error: Option<f64>,
+  pub max_abs_error: Option<f64>,
+  pub points: Vec<MrcPoint>,
+}
+
+impl MissRatioCurve {
+  /// Collects the estimates of every table size, compared to the exact
+  /// miss ratios if given. Fails if no requests were sampled
+  pub fn new(shards: &Shards, table_sizes: &[usize], exact: Option<Vec<f64>>)
+    -> Result<Self> {
+    if shards.sampled_requests() == 0 {
+      return Err(format!("None of the {} requests were sampled, try a higher --rate",
+        shards.requests()).into());
+    }
+
+    let points: Vec<MrcPoint> = table_sizes
+      .iter()
+      .enumerate()
+      .map(|(i, &table_size)| {
+        let miss_ratio = shards.miss_ratio(table_size);
+        let exact_miss_ratio = exact.as_ref().map(|x| x[i]);
+        MrcPoint {
+          table_size,
+          miss_ratio,
+          std_error: shards.std_error(table_size),
+          exact_miss_ratio,
+          error: exact_miss_ratio.map(|x| miss_ratio - x),
+        }
+      })
+      .collect();
+
+    let errors: Vec<f64> = points.iter().filter_map(|x| x.error.map(f64::abs)).collect();
+    let (mean_abs_error, max_abs_error) = if errors.is_empty() {
+      (None, None)
+    } else {
+      (Some(errors.iter().sum::<f64>() / errors.len() as f64),
+        Some(errors.iter().cloned().fold(0.0, f64::max)))
+    };
+
+    Ok(MissRatioCurve {
+      requests: shards.requests(),
+      sampled_requests: shards.sampled_requests(),
+      sampled_pages: shards.sampled_pages(),
+      rate: shards.rate(),
+      mean_abs_error,
+      max_abs_error,
+      points,
+    })
+  }
+
+  /// Writes a summary of the sample and a table of the miss ratios
+  pub fn write_text<W: Write>(&self, out: &mut W) -> Result<()> {
+    writeln!(out, "{:<18}{}", "Requests", self.requests)?;
+    writeln!(out, "{:<18}{}", "Sampled requests", self.sampled_requests)?;
+    writeln!(out, "{:<18}{}", "Sampled pages", self.sampled_pages)?;
+    writeln!(out, "{:<18}{:.5}", "Sampling rate", self.rate)?;
+    if let (Some(mean), Some(max)) = (self.mean_abs_error, self.max_abs_error) {
+      writeln!(out, "{:<18}{:.5}", "Mean abs error", mean)?;
+      writeln!(out, "{:<18}{:.5}", "Max abs error", max)?;
+    }
+
+    write!(out, "\n{:<12}{:<12}{:<12}", "table size", "miss ratio", "std error")?;
+    if self.mean_abs_error.is_some() {
+      write!(out, "{:<12}error", "exact")?;
+    }
+    writeln!(out)?;
+
+    for x in &self.points {
+      write!(out, "{:<12}{:<12.5}{:<12.5}", x.table_size, x.miss_ratio, x.std_error)?;
+      if let (Some(exact), Some(error)) = (x.exact_miss_ratio, x.error) {
+        write!(out, "{:<12.5}{:.5}", exact, error)?;
+      }
+      writeln!(out)?;
+    }
+
+    Ok(())
+  }
+
+  /// Writes the summary and every miss ratio as a single JSON object
+  pub fn write_json<W: Write>(&self, out: &mut W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, self)?;
+    writeln!(out)?;
+
+    Ok(())
+  }
+
+  /// Writes a csv row for every table size
+  pub fn write_csv<W: Write>(&self, out: W) -> Result<()> {
+    let mut wtr = Writer::from_writer(out);
+    for x in &self.points {
+      wtr.serialize(x)?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+  }
+}
+
+/// Estimates LRU miss ratios of the trace for every table size, reading the
+/// trace once without keeping it in memory unless validating
+pub fn mrc(options: MrcOptions) -> Result<()> {
+  let reader: Box<dyn BufRead> = match options.input {
+    Some(input) => {
+      info!("Reading page accesses from file {}", input);
+      Box::new(BufReader::new(File::open(input)?))
+    },
+    None => Box::new(BufReader::new(io::stdin())),
+  };
+
+  let mut shards = Shards::new(options.sampling);
+  let mut requests = Vec::new();
+  for line in reader.lines() {
+    if let Some(page) = util::parse_page_request(&line?) {
+      shards.access(page);
+      if options.validate {
+        requests.push(page);
+      }
+    }
+  }
+  info!("Sampled {} of {} requests with rate {:.5}", shards.sampled_requests(),
+    shards.requests(), shards.rate());
+
+  let exact = if options.validate {
+    Some(exact_miss_ratios(&requests, &options.table_sizes))
+  } else {
+    None
+  };
+  let curve = MissRatioCurve::new(&shards, &options.table_sizes, exact)?;
+  if let Some(error) = curve.mean_abs_error {
+    info!("Mean absolute error against the exact miss ratios: {:.5}", error);
+  }
+
+  let mut out: Box<dyn Write> = match options.output {
+    Some(output) => Box::new(File::create(output)?),
+    None => Box::new(io::stdout()),
+  };
+
+  match options.format {
+    "json" => curve.write_json(&mut out)?,
+    "csv" => curve.write_csv(&mut out)?,
+    _ => curve.write_text(&mut out)?,
+  }
+
+  if let Some(output) = options.output {
+    info!("Saved miss ratio curve to {}", output);
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use model::simulation::Simulation;
+  use super::*;
+
+  /// Same as the trace of the textbook examples of the algorithms
+  const TEXTBOOK_TRACE: [u64; 20] = [7, 0, 1, 2, 0, 3, 0, 4, 2, 3, 0, 3, 2, 1, 2, 0, 1, 7, 0, 1];
+
+  /// 3 in 4 requests are for 500 hot pages, the rest for 50000 pages. A
+  /// synthetic stand in for a long trace, accesses.txt is not in the repo
+  fn skewed_trace(len: u64) -> Vec<u64> {
+    (0..len)
+      .map(|i| {
+        let x = i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32;
+        if x % 4 == 0 { x % 50_000 } else { x % 500 }
+      })
+      .collect()
+  }
+
+  #[test]
+  fn exact_miss_ratios_match_lru() {
+    let sizes: Vec<usize> = (1..=8).collect();
+    for (&size, miss_ratio) in sizes.iter().zip(exact_miss_ratios(&TEXTBOOK_TRACE, &sizes)) {
+      let mut sim = Simulation::new(size, "lru", None).unwrap();
+      for &page in &TEXTBOOK_TRACE {
+        sim.request(page).unwrap();
+      }
+      assert_eq!(miss_ratio, sim.num_misses() as f64 / 20.0);
+    }
+  }
+
+  #[test]
+  fn shards_estimates_lru_miss_ratios() {
+    let trace = skewed_trace(200_000);
+    let sizes = [100, 400, 1000, 4000, 10_000, 40_000];
+    let exact = exact_miss_ratios(&trace, &sizes);
+
+    // errors are within 3 standard errors, or exact if every page is
+    // sampled. Smaller sizes are below the resolution of the sample
+    let check = |sampling| {
+      let mut shards = Shards::new(sampling);
+      for &page in &trace {
+        shards.access(page);
+      }
+
+      for (i, &size) in sizes.iter().enumerate().filter(|x| *x.1 as f64 * shards.rate() >= 10.0) {
+        let error = (shards.miss_ratio(size) - exact[i]).abs();
+        assert!(error <= 3.0 * shards.std_error(size), "{:?} {}: {}", sampling, size, error);
+      }
+    };
+
+    check(Sampling::Rate(1.0));
+    check(Sampling::FixedSize(60_000));
+    check(Sampling::Rate(0.1));
+    check(Sampling::Rate(0.01));
+    check(Sampling::FixedSize(2000));
+  }
+
+  #[test]
+  fn fixed_size_sample_memory_is_bounded() {
+    let mut shards = Shards::new(Sampling::FixedSize(100));
+    for page in skewed_trace(100_000) {
+      shards.access(page);
+      assert!(shards.stack.latest.len() <= 2 * shards.sampled_pages() + MIN_POSITIONS);
+    }
+    assert!(shards.sampled_pages() <= 100);
+  }
+
+  #[test]
+  fn empty_sample_has_no_curve() {
+    let mut shards = Shards::new(Sampling::Rate(0.0));
+    for page in 0..100 {
+      shards.access(page);
+    }
+
+    assert!(shards.miss_ratio(10).is_nan());
+    assert!(MissRatioCurve::new(&shards, &[10], None).is_err());
+  }
+}
@@ -0,0 +1,190 @@
+use model::simulation::SimulationResult;
+use indicatif::{
+  ProgressBar,
+  ProgressDrawTarget,
+  ProgressStyle,
+};
+use parking_lot::Mutex;
+use serde_json;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Time between json progress lines of running simulations
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A single json progress line
+#[derive(Serialize)]
+struct ProgressRecord<'a> {
+  /// Either `progress` once per second while simulations run, `simulation`
+  /// when a simulation completes or `finished`
+  event: &'a str,
+  completed: u64,
+  total: u64,
+  elapsed_secs: f64,
+  /// Page requests simulated per second over all simulations, including
+  /// the requests of simulations still running
+  requests_per_sec: f64,
+  /// Estimated seconds until all simulations are finished
+  eta_secs: Option<f64>,
+  /// Completed simulation, not included in the finished line
+  algorithm: Option<&'a str>,
+  table_size: Option<usize>,
+  hit_rate: Option<f64>,
+  /// Time spent on the completed simulation
+  simulation_secs: Option<f64>,
+}
+
+/// Progress of a json reporter
+pub struct JsonProgress {
+  start: Instant,
+  /// Number of completed simulations
+  completed: Mutex<u64>,
+  /// Total number of simulations
+  total: u64,
+  /// Number of page requests per simulation
+  requests: u64,
+  /// Number of page requests simulated so far
+  simulated: AtomicU64,
+  /// Time the last progress line was written
+  last_line: Mutex<Instant>,
+}
+
+impl JsonProgress {
+  /// Writes a progress line to stderr, `simulation` is the algorithm and
+  /// result of a completed simulation
+  fn write(&self, completed: u64, event: &str, simulation: Option<(&str, &SimulationResult)>) {
+    let elapsed_secs = self.start.elapsed().as_secs_f64();
+    let simulated = self.simulated.load(Ordering::Relaxed);
+    let requests_per_sec = if elapsed_secs > 0.0 {
+      simulated as f64 / elapsed_secs
+    } else {
+      0.0
+    };
+
+    let eta_secs = if requests_per_sec > 0.0 {
+      let remaining = (self.total * self.requests).saturating_sub(simulated);
+      Some(remaining as f64 / requests_per_sec)
+    } else {
+      None
+    };
+
+    let record = ProgressRecord {
+      event,
+      completed,
+      total: self.total,
+      elapsed_secs,
+      requests_per_sec,
+      eta_secs,
+      algorithm: simulation.map(|x| x.0),
+      table_size: simulation.map(|x| x.1.table_size),
+      hit_rate: simulation.map(|x| x.1.hit_rate),
+      simulation_secs: simulation.map(|x| x.1.elapsed_secs),
+    };
+
+    // progress is not important enough to stop simulations on errors
+    if let Ok(line) = serde_json::to_string(&record) {
+      let stderr = io::stderr();
+      let mut handle = stderr.lock();
+      let _ = writeln!(handle, "{}", line);
+    }
+  }
+}
+
+/// Reports progress of a range of simulations, either with an animated bar
+/// or json lines on stderr
+pub enum Progress {
+  /// Animated bar and the total number of simulations
+  Bar(ProgressBar, u64),
+  Json(JsonProgress),
+  Hidden,
+}
+
+impl Progress {
+  /// Creates a progress reporter for `total` simulations of `requests` page
+  /// requests each. `auto` uses the bar only if stderr is a terminal
+  pub fn new(mode: &str, total: u64, requests: u64) -> Self {
+    let mode = match mode {
+      "auto" if io::stderr().is_terminal() => "bar",
+      "auto" => "none",
+      mode => mode,
+    };
+
+    match mode {
+      "bar" => {
+        // create a new progress bar
+        let progress_bar = ProgressBar::new(total);
+        let sty = ProgressStyle::default_bar()
+          .template("[{elapsed_precise}] ETA {eta} {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
+          .progress_chars("##-");
+
+        progress_bar.set_style(sty);
+        progress_bar.set_draw_target(ProgressDrawTarget::stderr());
+
+        Progress::Bar(progress_bar, total)
+      },
+      "json" => Progress::Json(JsonProgress {
+        start: Instant::now(),
+        completed: Mutex::new(0),
+        total,
+        requests,
+        simulated: AtomicU64::new(0),
+        last_line: Mutex::new(Instant::now()),
+      }),
+      _ => Progress::Hidden,
+    }
+  }
+
+  /// Reports that a simulation has started
+  pub fn start(&self, algorithm: &str, table_size: usize) {
+    if let Progress::Bar(ref bar, _) = *self {
+      bar.set_message(&format!("Simulating {} table size {}",
+        algorithm.to_uppercase(), table_size));
+    }
+  }
+
+  /// Reports page requests simulated by a running simulation, writes a
+  /// json line if the last one is older than a second
+  pub fn advance(&self, requests: u64) {
+    if let Progress::Json(ref progress) = *self {
+      progress.simulated.fetch_add(requests, Ordering::Relaxed);
+
+      // another thread is already writing a line
+      if let Some(mut last_line) = progress.last_line.try_lock() {
+        if last_line.elapsed() >= PROGRESS_INTERVAL {
+          *last_line = Instant::now();
+          let completed = progress.completed.lock();
+          progress.write(*completed, "progress", None);
+        }
+      }
+    }
+  }
+
+  /// Reports a completed simulation and its result
+  pub fn complete(&self, algorithm: &str, result: &SimulationResult) {
+    match *self {
+      Progress::Bar(ref bar, _) => bar.inc(1),
+      Progress::Json(ref progress) => {
+        // hold lock while writing so lines stay in order
+        let mut completed = progress.completed.lock();
+        *completed += 1;
+        progress.write(*completed, "simulation", Some((algorithm, result)));
+      },
+      Progress::Hidden => {},
+    }
+  }
+
+  /// Reports that all simulations are finished
+  pub fn finish(&self) {
+    match *self {
+      Progress::Bar(ref bar, total) => {
+        bar.finish_with_message(&format!("Finished {} simulations", total));
+      },
+      Progress::Json(ref progress) => {
+        let completed = progress.completed.lock();
+        progress.write(*completed, "finished", None);
+      },
+      Progress::Hidden => {},
+    }
+  }
+}
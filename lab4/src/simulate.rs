@@ -1,147 +1,579 @@
-use error::Result;
-use indicatif::{
-  ProgressBar,
-  ProgressDrawTarget,
-  ProgressStyle,
-};
+use checkpoint::{Checkpoint, RunSettings};
+use ctrlc;
+use error::{Error, Result};
+use events::{Event, EventWriter};
+use model::algorithms::{self, AlgorithmParams};
+use log::Level;
+use model::observer::{LogObserver, StdoutObserver};
+use model::page::PageId;
+use model::prefetch::Prefetcher;
+use model::report::{PageReport, ReportThresholds};
 use model::simulation::*;
-use parking_lot::{Mutex, RwLock};
+use parking_lot::Mutex;
+use progress::Progress;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
+use std::mem;
+use std::process;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
 use threadpool::Builder;
+use util;
 
-/// Runs a simulation or simulations for a range of table sizes,
+/// Max number of stdin page requests sent to simulation workers at once,
+/// fewer are sent if no more input is available yet
+const STDIN_BATCH_SIZE: usize = 4096;
+
+/// Number of page requests between checks if simulations were interrupted
+const CANCEL_CHECK_INTERVAL: usize = 4096;
+
+/// Results for each algorithm, in the order the algorithms were given
+pub type SimulationResults = Vec<(String, Vec<SimulationResult>)>;
+
+/// Runs a parsed trace line on a simulation, writing its event if an
+/// events file is used. Events are disabled if they can't be written
+fn run_entry<P: PageId>(sim: &mut Simulation<P>, entry: TraceEntry<P>,
+  events: &mut Option<EventWriter>) -> Result<()> {
+  // only keep the page for the event when events are written
+  let page = match entry {
+    TraceEntry::Request(ref request) if events.is_some() => Some(request.page.clone()),
+    _ => None,
+  };
+  let outcome = sim.run_entry(entry)?;
+
+  if let (Some(outcome), Some(page)) = (outcome, page) {
+    let res = match *events {
+      Some(ref mut wtr) => wtr.write(&Event::new(sim.num_requests() - 1, page, outcome)),
+      None => Ok(()),
+    };
+
+    if let Err(e) = res {
+      error!("Failed to write events: {}", e);
+      *events = None;
+    }
+  }
+
+  Ok(())
+}
+
+/// Adds the built-in observers, printing to stdout if requested and
+/// logging requests when debug logging is enabled
+fn add_observers<P: PageId>(sim: &mut Simulation<P>, should_stdout: bool) {
+  if should_stdout {
+    sim.add_observer(Box::new(StdoutObserver));
+  }
+
+  if log_enabled!(Level::Debug) {
+    sim.add_observer(Box::new(LogObserver::default()));
+  }
+}
+
+/// Adds a page report to a simulation if a page report file is written
+fn add_page_report<P: PageId>(sim: &mut Simulation<P>, output: Option<&str>,
+  thresholds: ReportThresholds) -> Option<Arc<Mutex<PageReport<P>>>> {
+  output.map(|_| {
+    let report = Arc::new(Mutex::new(PageReport::new(thresholds)));
+    sim.add_observer(Box::new(report.clone()));
+    report
+  })
+}
+
+/// Saves the page report of a finished simulation, errors are only logged
+/// since the simulation results are still valid
+fn save_page_report<P: PageId>(report: Option<Arc<Mutex<PageReport<P>>>>, output: Option<&str>,
+  algorithm: &str, table_size: usize) {
+  if let (Some(report), Some(output)) = (report, output) {
+    let pages = report.lock().summaries();
+    if let Err(e) = util::save_page_report(output, algorithm, table_size, &pages) {
+      error!("Failed to save page report for table size {}: {}", table_size, e);
+    }
+  }
+}
+
+/// Groups results by algorithm, keeping the order of the given algorithms.
+/// Results of the same table size run with different seeds are averaged
+fn group_results(algorithms: &[&str], results: Vec<(String, u64, SimulationResult)>)
+  -> SimulationResults {
+  algorithms
+    .iter()
+    .map(|&algorithm| {
+      let mut sizes = BTreeMap::new();
+      for x in results.iter().filter(|x| x.0 == algorithm) {
+        sizes.entry(x.2.table_size).or_insert_with(Vec::new).push(x.2.clone());
+      }
+
+      let algorithm_results = sizes
+        .values()
+        .map(|x| SimulationResult::mean(x))
+        .collect();
+
+      (algorithm.to_string(), algorithm_results)
+    })
+    .collect()
+}
+
+/// Stops simulations on the first Ctrl-C, simulations that have not
+/// finished yet are skipped. A second Ctrl-C exits immediately
+fn handle_interrupt() -> Arc<AtomicBool> {
+  let cancelled = Arc::new(AtomicBool::new(false));
+  let handler_cancelled = cancelled.clone();
+
+  let res = ctrlc::set_handler(move || {
+    if handler_cancelled.swap(true, Ordering::SeqCst) {
+      process::exit(130);
+    }
+
+    warn!("Interrupted, stopping simulations. Press Ctrl-C again to exit immediately");
+  });
+
+  // simulations still work without the handler, they just can't be stopped early
+  if let Err(e) = res {
+    warn!("Failed to set Ctrl-C handler: {}", e);
+  }
+
+  cancelled
+}
+
+/// Runs a simulation or simulations for each table size and algorithm,
 /// buffers input via a file given to allow for page request input reuse
-fn simulate_file(options: SimulationOptions) -> Result<Vec<(usize, f64)>> {
+fn simulate_file<P: PageId>(options: SimulationOptions, pins: Arc<Vec<ScheduledPin<P>>>)
+  -> Result<SimulationResults> {
 
   // destructure options struct
   let SimulationOptions {
     input,
-    table_size,
-    to_table_size,
-    algorithm,
+    table_sizes,
+    algorithms,
     should_stdout,
+    events,
+    events_format,
+    page_report,
+    report_thresholds,
+    progress,
+    jobs,
+    checkpoint,
+    resume,
+    cost,
+    prefetcher,
+    format,
+    params,
+    repeat,
+    ..
   } = options;
 
   let file_name = input.unwrap(); // checked before so ok to unwrap
-  // use to table size or just use same as table_size
-  let to_table_size = to_table_size.unwrap_or(table_size);
 
   let file = File::open(file_name)?;
   info!("Reading page accesses from file {}", &file_name);
   let reader = BufReader::new(file);
 
-  // Vec of page requests from file
-  let mut page_requests = Vec::new();
-  
+  // parsed trace lines from file
+  let mut entries = Vec::new();
+
   // read input from file to a vec first to allow for
   // repeat use for different memory sizes
   for line in reader.lines() {
-    let line = line?;
-    page_requests.push(line);
+    format.parse_line::<P>(&line?, &mut entries);
   }
+  format.finish();
+
+  // optimal looks ahead in the page requests only, directives are left out
+  // so its position follows the number of requests
+  let future = if algorithms.contains(&"optimal") {
+    let requests = entries
+      .iter()
+      .filter_map(|x| match *x {
+        TraceEntry::Request(ref request) => Some(request.page.clone()),
+        _ => None,
+      })
+      .collect();
+    Some(Arc::new(requests))
+  } else {
+    None
+  };
 
-  // vec of page requests, same content as the input file
-  // not really necessary to use a rwlock since we are not
-  // modifying the vec after reading the file above
-  let page_requests = Arc::new(RwLock::new(page_requests));
+  // entries are shared between simulations, not modified after reading
+  let entries = Arc::new(entries);
 
-  // thread safe hit rates
+  // thread safe results
   // mutex in atomically referenced counted pointer
-  let hit_rates = Arc::new(Mutex::new(Vec::new()));
+  let results = Arc::new(Mutex::new(Vec::new()));
+  // first error of a failed simulation
+  let failed = Arc::new(Mutex::new(None));
 
-  // build threadpool, # threads = cpu count
-  let pool = Builder::new()
-    .thread_name("simulation_worker".into())
-    .build();
+  // build threadpool, # threads = cpu count unless set
+  let mut builder = Builder::new()
+    .thread_name("simulation_worker".into());
+  if let Some(num_threads) = jobs {
+    builder = builder.num_threads(num_threads);
+  }
+  let pool = builder.build();
   
   info!("Using {} threads for concurrent simulations", pool.max_count());
 
-  let num_simulations = if to_table_size != table_size {
-    (to_table_size - table_size) as u64
-  } else {
-    1
-  };
+  let mut jobs = simulation_jobs(&algorithms, &table_sizes, params.seed, repeat);
+  let settings = RunSettings::new(cost, &prefetcher, &params);
+
+  // skip simulations completed by a previous run
+  if resume {
+    // checked before so ok to unwrap
+    let mut saved = Checkpoint::load(checkpoint.unwrap(), &settings)?;
+    // sizes, algorithms and seeds that are not part of this run are left out
+    saved.retain(|x| jobs.iter()
+      .any(|job| x.0 == job.0 && x.2.table_size == job.1 && x.1 == job.2));
+    jobs.retain(|job| !saved.iter()
+      .any(|x| x.0 == job.0 && x.2.table_size == job.1 && x.1 == job.2));
+    info!("Resuming with {} completed simulations, {} remaining", saved.len(), jobs.len());
+    results.lock().extend(saved);
+  }
 
-  // create a new progress bar
-  let progress_bar = ProgressBar::new(num_simulations);
-  let sty = ProgressStyle::default_bar()
-    .template("[{elapsed_precise}] ETA {eta} {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-    .progress_chars("##-");
+  let checkpoint = match checkpoint {
+    Some(path) => Some(Arc::new(Mutex::new(Checkpoint::create(path, resume, settings)?))),
+    None => None,
+  };
 
-  progress_bar.set_style(sty);
-  progress_bar.set_draw_target(ProgressDrawTarget::stderr());
+  let num_simulations = jobs.len() as u64;
+  let cancelled = handle_interrupt();
 
-  // put bar in an arc to allow for multi thread references
-  let bar = Arc::new(progress_bar);
+  // put progress in an arc to allow for multi thread references
+  let num_requests = entries.len() as u64;
+  let bar = Arc::new(Progress::new(progress, num_simulations, num_requests));
 
-  // repeat for table size range
-  for curr_table_size in table_size..=to_table_size {
+  // repeat for each algorithm, table size range and seed
+  for (algorithm, curr_table_size, seed) in jobs {
     // clone arc pointers to be moved into new thread
-    let page_requests = page_requests.clone();
-    let hit_rates = hit_rates.clone();
-    let algorithm = algorithm.to_string();
+    let entries = entries.clone();
+    let future = future.clone();
+    let results = results.clone();
+    let events = events.map(|x| x.to_string());
+    let events_format = events_format.to_string();
+    let page_report = page_report.map(|x| x.to_string());
     let bar = bar.clone();
+    let checkpoint = checkpoint.clone();
+    let cancelled = cancelled.clone();
+    let prefetcher = prefetcher.clone();
+    let pins = pins.clone();
+    let failed = failed.clone();
     // run on threadpool
     pool.execute(move || {
+      if cancelled.load(Ordering::SeqCst) {
+        return;
+      }
+
       // info!("Running simulation with table size {}", curr_table_size);
-      bar.set_message(&format!("Simulating table size {}", curr_table_size));
-      let mut sim = Simulation::new(curr_table_size, &algorithm, Some(page_requests.clone()));
-      // iterate over file lines
-      let reader = page_requests.read();
-      for page_request in reader.iter() {
-        sim.page_request(page_request, should_stdout);
+      bar.start(&algorithm, curr_table_size);
+      let start = Instant::now();
+      let params = AlgorithmParams { seed, ..params };
+      let mut sim = match Simulation::with_params(curr_table_size, &algorithm, &params,
+        future) {
+        Ok(sim) => sim,
+        Err(e) => {
+          failed.lock().get_or_insert(e);
+          return;
+        },
+      };
+      sim.set_prefetcher(prefetcher);
+      sim.set_pins(pins);
+      add_observers(&mut sim, should_stdout);
+      let report = add_page_report(&mut sim, page_report.as_deref(), report_thresholds);
+
+      // events file for this table size, fails like the stdin simulations
+      // if it can't be created
+      let mut events = match events {
+        Some(output) => {
+          match EventWriter::create(&output, &events_format, &algorithm, curr_table_size) {
+            Ok(wtr) => Some(wtr),
+            Err(e) => {
+              failed.lock().get_or_insert(e);
+              return;
+            },
+          }
+        },
+        None => None,
+      };
+
+      // iterate over trace entries
+      let mut reported = 0;
+      for (i, entry) in entries.iter().enumerate() {
+        if i % CANCEL_CHECK_INTERVAL == 0 {
+          // unfinished simulations are dropped, they are run again on resume
+          if cancelled.load(Ordering::SeqCst) {
+            return;
+          }
+
+          bar.advance((i - reported) as u64);
+          reported = i;
+        }
+
+        if let Err(e) = run_entry(&mut sim, entry.clone(), &mut events) {
+          failed.lock().get_or_insert(Error::Paging(format!("{} with table size {}: {}",
+            algorithm.to_uppercase(), curr_table_size, e)));
+          return;
+        }
+      }
+
+      bar.advance((entries.len() - reported) as u64);
+
+      if let Some(mut wtr) = events {
+        if let Err(e) = wtr.flush() {
+          error!("Failed to write events for table size {}: {}", curr_table_size, e);
+        }
+      }
+
+      let result = sim.finish(curr_table_size, &cost, start.elapsed().as_secs_f64());
+      save_page_report(report, page_report.as_deref(), &algorithm, curr_table_size);
+      bar.complete(&algorithm, &result);
+
+      if let Some(checkpoint) = checkpoint {
+        if let Err(e) = checkpoint.lock().write(&algorithm, seed, &result) {
+          error!("Failed to write checkpoint for table size {}: {}", curr_table_size, e);
+        }
       }
 
       {
-        // push hit rate to vec
-        let mut guard = hit_rates.lock();
-        guard.push((curr_table_size, sim.get_hit_rate(should_stdout)));
+        // push result to vec
+        let mut guard = results.lock();
+        guard.push((algorithm, seed, result));
       }
-      bar.inc(1);
     })
   }
 
   // wait until jobs finished
   pool.join();
-  bar.finish_with_message(&format!("Finished {} simulations", num_simulations));
+  bar.finish();
+
+  if cancelled.load(Ordering::SeqCst) {
+    warn!("Simulations were interrupted, only completed simulations are saved");
+  }
+
+  // completed simulations are still in the checkpoint
+  if let Some(e) = failed.lock().take() {
+    return Err(e);
+  }
 
   // get inner value of Arc<Mutex> and clone, 
   // probably not ideal to clone the data but hard to take ownership
   // of T in Arc<Mutex<T>> after wrapping it
-  Ok(Arc::try_unwrap(hit_rates).unwrap().into_inner().clone())
+  let results = Arc::try_unwrap(results).unwrap().into_inner().clone();
+  Ok(group_results(&algorithms, results))
+}
+
+/// Lists the algorithm, table size and seed of every simulation to run,
+/// largest table sizes first. Randomized algorithms are run `repeat` times
+/// with the seeds following `seed`, other algorithms only once. Every
+/// algorithm searches the whole table on each request so larger tables
+/// take longer, starting them first prevents a few large simulations from
+/// running alone at the end
+fn simulation_jobs(algorithms: &[&str], table_sizes: &[usize], seed: u64, repeat: usize)
+  -> Vec<(String, usize, u64)> {
+  let mut jobs: Vec<(String, usize, u64)> = algorithms
+    .iter()
+    .flat_map(|&algorithm| {
+      let runs = if algorithms::is_randomized(algorithm) { repeat as u64 } else { 1 };
+      table_sizes
+        .iter()
+        .flat_map(move |&size| (seed..seed + runs)
+          .map(move |seed| (algorithm.to_string(), size, seed)))
+    })
+    .collect();
+
+  // stable sort, keeps algorithm and seed order for the same size
+  jobs.sort_by_key(|x| std::cmp::Reverse(x.1));
+  jobs
 }
 
-/// Runs a single simulation without input buffering to allow for immediate
-/// feedback per page request, main use case for testing
-fn simulate_stdin(table_size: usize, algorithm: &str, should_stdout: bool) -> Result<f64> {
-  let mut sim = Simulation::new(table_size, algorithm, None);
-  let stdin = io::stdin();
+/// Checks if the results hold every table size of every algorithm, with
+/// all `repeat` runs of randomized algorithms. Results are missing when
+/// the simulations were interrupted
+pub fn is_complete(results: &SimulationResults, table_sizes: &[usize], repeat: usize) -> bool {
+  results.iter().all(|(algorithm, algorithm_results)| {
+    let runs = if algorithms::is_randomized(algorithm) { repeat } else { 1 };
+    algorithm_results.len() == table_sizes.len()
+      && algorithm_results.iter().all(|x| x.runs == runs)
+  })
+}
+
+/// Options shared by the simulations of a stdin worker thread
+struct WorkerOptions<P> {
+  should_stdout: bool,
+  cost: CostModel,
+  prefetcher: Option<Prefetcher>,
+  pins: Arc<Vec<ScheduledPin<P>>>,
+  params: AlgorithmParams,
+  events: Option<String>,
+  events_format: String,
+  page_report: Option<String>,
+  report_thresholds: ReportThresholds,
+}
+
+/// Runs a group of simulations on a worker thread, receiving batches of
+/// trace lines until the sender is dropped
+fn stdin_worker<P: PageId>(jobs: Vec<(String, usize, u64)>,
+  requests: mpsc::Receiver<Arc<Vec<TraceEntry<P>>>>, options: WorkerOptions<P>)
+  -> Result<Vec<(String, u64, SimulationResult)>> {
+  let mut sims = Vec::new();
+  for (algorithm, table_size, seed) in jobs {
+    let params = AlgorithmParams { seed, ..options.params };
+    let mut sim = Simulation::with_params(table_size, &algorithm, &params, None)?;
+    sim.set_prefetcher(options.prefetcher.clone());
+    sim.set_pins(options.pins.clone());
+    add_observers(&mut sim, options.should_stdout);
+    let report = add_page_report(&mut sim, options.page_report.as_deref(),
+      options.report_thresholds);
+    let events = match options.events {
+      Some(ref output) => Some(EventWriter::create(output, &options.events_format,
+        &algorithm, table_size)?),
+      None => None,
+    };
 
-  // iterate over input lines
-  for line in stdin.lock().lines() {
-    let page_request = line?;
-    sim.page_request(&page_request, should_stdout);
+    // time spent on this simulation, simulations take turns on each batch
+    let elapsed = 0.0;
+    sims.push((algorithm, table_size, seed, sim, events, report, elapsed));
+  }
+
+  for batch in requests {
+    for &mut (ref algorithm, table_size, _, ref mut sim, ref mut events, _, ref mut elapsed)
+      in sims.iter_mut() {
+      let start = Instant::now();
+      for entry in batch.iter() {
+        run_entry(sim, entry.clone(), events)
+          .map_err(|e| Error::Paging(format!("{} with table size {}: {}",
+            algorithm.to_uppercase(), table_size, e)))?;
+      }
+      *elapsed += start.elapsed().as_secs_f64();
+    }
   }
 
-  Ok(sim.get_hit_rate(should_stdout))
+  let mut results = Vec::new();
+  for (algorithm, table_size, seed, mut sim, events, report, elapsed_secs) in sims {
+    if let Some(mut wtr) = events {
+      wtr.flush()?;
+    }
+
+    let result = sim.finish(table_size, &options.cost, elapsed_secs);
+    save_page_report(report, options.page_report.as_deref(), &algorithm, table_size);
+    results.push((algorithm, seed, result));
+  }
+
+  Ok(results)
+}
+
+/// Runs simulations for each table size and algorithm on page
+/// requests from stdin without buffering the whole input. Requests are
+/// sent to all simulations as they are read, allowing for immediate
+/// feedback per page request and piping in live traces
+fn simulate_stdin<P: PageId>(options: &SimulationOptions, pins: Arc<Vec<ScheduledPin<P>>>)
+  -> Result<SimulationResults> {
+  if options.resume {
+    return Err("Resuming requires page requests from an input file (-i file)".into());
+  }
+
+  if options.algorithms.contains(&"optimal") {
+    return Err("Optimal requires page requests from an input file (-i file)".into());
+  }
+
+  let jobs = simulation_jobs(&options.algorithms, &options.table_sizes, options.params.seed,
+    options.repeat);
+
+  // # threads = cpu count unless set, or less if there are fewer simulations
+  let num_workers = options.jobs
+    .or_else(|| thread::available_parallelism().map(|x| x.get()).ok())
+    .unwrap_or(1)
+    .min(jobs.len());
+  info!("Using {} threads for concurrent simulations", num_workers);
+
+  // spread simulations evenly over the workers, each worker gets its
+  // own channel and every worker receives every request
+  let mut senders = Vec::new();
+  let mut workers = Vec::new();
+  for worker in 0..num_workers {
+    let (sender, receiver) = mpsc::channel();
+    let worker_jobs = jobs.iter().skip(worker).step_by(num_workers).cloned().collect();
+    let worker_options = WorkerOptions {
+      should_stdout: options.should_stdout,
+      cost: options.cost,
+      prefetcher: options.prefetcher.clone(),
+      pins: pins.clone(),
+      params: options.params,
+      events: options.events.map(|x| x.to_string()),
+      events_format: options.events_format.to_string(),
+      page_report: options.page_report.map(|x| x.to_string()),
+      report_thresholds: options.report_thresholds,
+    };
+
+    let handle = thread::Builder::new()
+      .name("simulation_worker".into())
+      .spawn(move || stdin_worker(worker_jobs, receiver, worker_options))?;
+
+    senders.push(sender);
+    workers.push(handle);
+  }
+
+  let mut reader = BufReader::new(io::stdin());
+  let mut line = String::new();
+  let mut batch = Vec::new();
+
+  loop {
+    line.clear();
+    let eof = reader.read_line(&mut line)? == 0;
+    options.format.parse_line::<P>(line.trim_end_matches('\n').trim_end_matches('\r'), &mut batch);
+
+    // send when nothing else has been read yet so slow live input isn't held back
+    if !batch.is_empty() && (eof || batch.len() >= STDIN_BATCH_SIZE || reader.buffer().is_empty()) {
+      let requests = Arc::new(mem::take(&mut batch));
+      // a worker only stops receiving on an error, which is returned when joined
+      if senders.iter().any(|x| x.send(requests.clone()).is_err()) {
+        break;
+      }
+    }
+
+    if eof {
+      break;
+    }
+  }
+  options.format.finish();
+
+  // dropping the senders lets the workers finish
+  drop(senders);
+
+  let mut results = Vec::new();
+  for handle in workers {
+    let worker_results = handle.join()
+      .map_err(|_| "Simulation worker panicked")??;
+    results.extend(worker_results);
+  }
+
+  Ok(group_results(&options.algorithms, results))
 }
 
 /// Checks if there is an input file and runs simulations,
 /// uses stdin input if no input file found
-pub fn simulate(options: SimulationOptions) -> Result<Vec<(usize, f64)>> {
-  if options.input.is_some() {
-    let hit_rates = simulate_file(options);
+pub fn simulate(options: SimulationOptions) -> Result<SimulationResults> {
+  match options.page_ids {
+    "u128" => simulate_pages::<u128>(options),
+    "string" => simulate_pages::<String>(options),
+    _ => simulate_pages::<u64>(options),
+  }
+}
+
+/// Runs simulations with page identifiers of type `P`, pins are read with
+/// the same type as the trace
+fn simulate_pages<P: PageId>(options: SimulationOptions) -> Result<SimulationResults> {
+  let pins = match options.pins {
+    Some(path) => util::read_pins::<P>(path)
+      .map_err(|e| Error::Paging(format!("Failed to read pins: {}", e)))?,
+    None => Vec::new(),
+  };
+  let pins = Arc::new(pins);
 
-    return hit_rates;
+  if options.input.is_some() {
+    return simulate_file(options, pins);
   }
 
   // no input file, read from stdin
-  let mut hit_rates = Vec::new();
-  let hit_rate = simulate_stdin(options.table_size,
-    options.algorithm, options.should_stdout);
-  hit_rates.push((options.table_size, hit_rate.unwrap()));
-
-  Ok(hit_rates)
+  simulate_stdin(&options, pins)
 }
@@ -1,6 +1,6 @@
 use chrono;
 use csv::Writer;
-use error::Result;
+use error::{Error, Result};
 use fern::{
   self,
   colors::{
@@ -9,10 +9,72 @@ use fern::{
   }
 };
 use log;
+use model::page::PageId;
+use model::report::PageSummary;
+use model::simulation::{PageRequest, ScheduledPin, SimulationResult, TraceEntry};
+use serde_json;
 use std;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
 
-/// Sets up the logger
-pub fn setup_logger(verbosity: u64) -> Result<()> {
+/// Options for logging output
+pub struct LogOptions<'a> {
+  /// Number of times `-v` was given
+  pub verbosity: u64,
+  /// File to also write logs to, only logs to stderr if None
+  pub file: Option<&'a str>,
+  /// Either `text` or `json`
+  pub format: &'a str,
+  /// Per module level filters, ex: `page_replacements::simulate=warn`
+  pub filters: Vec<&'a str>,
+}
+
+/// A single log message for json output
+#[derive(Serialize)]
+struct LogRecord<'a> {
+  time: String,
+  level: &'a str,
+  target: &'a str,
+  message: String,
+}
+
+/// Formats a log message as a json line
+fn format_json(out: fern::FormatCallback, message: &std::fmt::Arguments, record: &log::Record) {
+  let line = serde_json::to_string(&LogRecord {
+    time: chrono::Local::now().to_rfc3339(),
+    level: record.level().as_str(),
+    target: record.target(),
+    message: message.to_string(),
+  }).unwrap_or_default();
+
+  out.finish(format_args!("{}", line))
+}
+
+/// Parses a module level filter in the form of `module=level`
+fn parse_level_filter(filter: &str) -> Option<(&str, log::LevelFilter)> {
+  let mut split = filter.splitn(2, '=');
+  let module = split.next()?;
+  let level = split.next()?.parse().ok()?;
+
+  if module.is_empty() {
+    return None;
+  }
+
+  Some((module, level))
+}
+
+/// Validates a module level filter in the form of `module=level`
+pub fn validate_level_filter(filter: String) -> std::result::Result<(), String> {
+  if parse_level_filter(&filter).is_none() {
+    return Err("Please give a module and level, ex: page_replacements::simulate=warn".into());
+  }
+
+  Ok(())
+}
+
+/// Sets up the logger, logs to stderr and optionally a file. Failing to
+/// open the log file only logs a warning and continues with stderr only
+pub fn setup_logger(options: LogOptions) -> Result<()> {
   let colors = ColoredLevelConfig::new()
     .info(Color::BrightGreen)
     .debug(Color::BrightCyan)
@@ -20,7 +82,7 @@ pub fn setup_logger(verbosity: u64) -> Result<()> {
   
   let mut base_config = fern::Dispatch::new();
 
-  base_config = match verbosity {
+  base_config = match options.verbosity {
     0 => base_config
       .level(log::LevelFilter::Info),
     1 => base_config
@@ -29,26 +91,239 @@ pub fn setup_logger(verbosity: u64) -> Result<()> {
       .level(log::LevelFilter::Trace),
   };
 
-  base_config
-    .format(move |out, message, record| {
-      out.finish(format_args!(
-        "{}[{}] {}",
-        chrono::Local::now().format("[%H:%M:%S]"),
-        colors.color(record.level()),
-        message
-      ))
-    })
-    .chain(std::io::stderr())
-    .chain(fern::log_file("output.log")?)
-    .apply()?;
+  for filter in &options.filters {
+    if let Some((module, level)) = parse_level_filter(filter) {
+      base_config = base_config.level_for(module.to_string(), level);
+    }
+  }
+
+  let json = options.format == "json";
+
+  // only colour stderr, colours would end up as escape codes in the file
+  let stderr_config = if json {
+    fern::Dispatch::new().format(format_json)
+  } else {
+    fern::Dispatch::new()
+      .format(move |out, message, record| {
+        out.finish(format_args!(
+          "{}[{}] {}",
+          chrono::Local::now().format("[%H:%M:%S]"),
+          colors.color(record.level()),
+          message
+        ))
+      })
+  };
+  base_config = base_config.chain(stderr_config.chain(std::io::stderr()));
+
+  // keep error to log after the logger is set up
+  let mut file_error = None;
+  if let Some(path) = options.file {
+    match fern::log_file(path) {
+      Ok(file) => {
+        let file_config = if json {
+          fern::Dispatch::new().format(format_json)
+        } else {
+          fern::Dispatch::new()
+            .format(|out, message, record| {
+              out.finish(format_args!(
+                "{}[{}] {}",
+                chrono::Local::now().format("[%H:%M:%S]"),
+                record.level(),
+                message
+              ))
+            })
+        };
+        base_config = base_config.chain(file_config.chain(file));
+      },
+      Err(e) => file_error = Some((path, e)),
+    }
+  }
+
+  base_config.apply()?;
+
+  if let Some((path, e)) = file_error {
+    warn!("Failed to open log file {}, only logging to stderr: {}", path, e);
+  }
 
   Ok(())
 }
 
+/// Parses a page request line to a page, invalid lines are ignored
+pub fn parse_page_request<P: PageId>(line: &str) -> Option<P> {
+  parse_access(line).map(|x| x.page)
+}
+
+/// Parses a page request line, a page optionally followed by `r` or `w`
+/// for reads and writes: `5072 w`. Invalid lines are ignored
+pub fn parse_access<P: PageId>(line: &str) -> Option<PageRequest<P>> {
+  let mut split = line.split(|c: char| c.is_whitespace() || c == ',')
+    .filter(|x| !x.is_empty());
+
+  let page = split.next()?.parse::<P>().ok()?;
+
+  let write = match split.next() {
+    None | Some("r") | Some("R") => false,
+    Some("w") | Some("W") => true,
+    Some(_) => return None,
+  };
+
+  // nothing else is allowed after the access type
+  if split.next().is_some() {
+    return None;
+  }
+
+  Some(PageRequest { page, write })
+}
+
+/// Parses a pin directive: `pin 5072` or `unpin 5072`
+fn parse_directive<P: PageId>(line: &str) -> Option<TraceEntry<P>> {
+  let mut split = line.split_whitespace();
+  let directive = split.next()?;
+  let page = split.next()?.parse::<P>().ok()?;
+
+  if split.next().is_some() {
+    return None;
+  }
+
+  match directive {
+    "pin" => Some(TraceEntry::Pin(page)),
+    "unpin" => Some(TraceEntry::Unpin(page)),
+    _ => None,
+  }
+}
+
+/// Parses a trace line, either a page request or a pin directive
+pub fn parse_trace_line<P: PageId>(line: &str) -> Option<TraceEntry<P>> {
+  parse_access(line)
+    .map(TraceEntry::Request)
+    .or_else(|| parse_directive(line))
+}
+
+/// Reads a pin file, each line is a pin directive optionally after the
+/// number of page requests to run before it: `pin 5072` or `100 unpin 5072`.
+/// Pins without a position are applied before the first request
+pub fn read_pins<P: PageId>(path: &str) -> Result<Vec<ScheduledPin<P>>> {
+  let reader = BufReader::new(File::open(path)?);
+  let mut pins = Vec::new();
+
+  for (i, line) in reader.lines().enumerate() {
+    let line = line?;
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    // optional position before the directive
+    let (position, directive) = match line.split_once(char::is_whitespace) {
+      Some((position, rest)) if position.parse::<u64>().is_ok() =>
+        (position.parse::<u64>().unwrap(), rest.trim()),
+      _ => (0, line),
+    };
+
+    match parse_directive(directive) {
+      Some(entry) => pins.push(ScheduledPin { position, entry }),
+      None => return Err(Error::Paging(format!(
+        "Invalid pin on line {} of {}: {}", i + 1, path, line))),
+    }
+  }
+
+  // stable sort keeps the order of pins at the same position
+  pins.sort_by_key(|x| x.position);
+  info!("Read {} pins from {}", pins.len(), path);
+
+  Ok(pins)
+}
+
+/// Reads all valid page requests from a file, or stdin if no file is given
+pub fn read_requests(input: Option<&str>) -> Result<Vec<u64>> {
+  let mut requests = Vec::new();
+
+  let reader: Box<dyn BufRead> = match input {
+    Some(file_name) => {
+      info!("Reading page accesses from file {}", file_name);
+      Box::new(BufReader::new(File::open(file_name)?))
+    },
+    None => Box::new(BufReader::new(io::stdin())),
+  };
+
+  for line in reader.lines() {
+    if let Some(page) = parse_page_request(&line?) {
+      requests.push(page);
+    }
+  }
+
+  Ok(requests)
+}
+
+/// Parses an object request line, a key and its size in bytes:
+/// `index.html,5120`. Invalid lines are ignored
+pub fn parse_object_request(line: &str) -> Option<(String, u64)> {
+  let mut split = line.split(|c: char| c.is_whitespace() || c == ',')
+    .filter(|x| !x.is_empty());
+
+  let key = split.next()?.to_string();
+  let size = split.next()?.parse().ok()?;
+
+  if split.next().is_some() {
+    return None;
+  }
+
+  Some((key, size))
+}
+
+/// Reads all valid object requests from a file, or stdin if no file is given
+pub fn read_object_requests(input: Option<&str>) -> Result<Vec<(String, u64)>> {
+  let mut requests = Vec::new();
+
+  let reader: Box<dyn BufRead> = match input {
+    Some(file_name) => {
+      info!("Reading object requests from file {}", file_name);
+      Box::new(BufReader::new(File::open(file_name)?))
+    },
+    None => Box::new(BufReader::new(io::stdin())),
+  };
+
+  for line in reader.lines() {
+    if let Some(request) = parse_object_request(&line?) {
+      requests.push(request);
+    }
+  }
+
+  Ok(requests)
+}
+
+/// Parses a number of bytes with an optional binary unit, ex: 512, 64K,
+/// 10MB or 1GiB
+pub fn parse_bytes(bytes: &str) -> std::result::Result<u64, String> {
+  let upper = bytes.trim().to_uppercase();
+  let number = upper.trim_end_matches("IB").trim_end_matches('B');
+
+  let (number, shift) = match number.chars().last() {
+    Some('K') => (&number[..number.len() - 1], 10),
+    Some('M') => (&number[..number.len() - 1], 20),
+    Some('G') => (&number[..number.len() - 1], 30),
+    Some('T') => (&number[..number.len() - 1], 40),
+    _ => (number, 0),
+  };
+
+  number.parse::<u64>()
+    .ok()
+    .and_then(|x| x.checked_mul(1 << shift))
+    .ok_or_else(|| format!("Invalid number of bytes {}, ex: 512, 64K, 10M or 1G", bytes))
+}
+
+/// Validates a cache capacity in bytes greater than 0
+pub fn validate_capacity(capacity: String) -> std::result::Result<(), String> {
+  match parse_bytes(&capacity)? {
+    0 => Err("Please give a capacity over 0".into()),
+    _ => Ok(()),
+  }
+}
+
 /// Validates of a table size is both a number and greater than 0
 pub fn validate_table_size(size: String) -> std::result::Result<(), String> {
   if let Ok(parsed) = size.parse::<usize>() {
-    if parsed <= 0 {
+    if parsed == 0 {
       // don't think we can get negative numbers so this is
       // mainly just a check for 0
       return Err("Please give a number over 0".into());
@@ -60,25 +335,224 @@ pub fn validate_table_size(size: String) -> std::result::Result<(), String> {
   Ok(())
 }
 
-/// Saves a vec of hit rates to a csv file
-pub fn save_result(output: &str, algorithm: &str, mut hit_rates: Vec<(usize, f64)>)
+/// Validates if a count is a whole number over 0, ex: a number of threads
+/// or pages
+pub fn validate_positive(value: String) -> std::result::Result<(), String> {
+  match value.parse::<u64>() {
+    Ok(0) => Err("Please give a number over 0".into()),
+    Ok(_) => Ok(()),
+    Err(_) => Err("Please give a whole number".into()),
+  }
+}
+
+/// Validates if a latency is a number of nanoseconds that is not negative
+pub fn validate_latency(latency: String) -> std::result::Result<(), String> {
+  match latency.parse::<f64>() {
+    Ok(parsed) if parsed.is_finite() && parsed >= 0.0 => Ok(()),
+    Ok(_) => Err("Please give a latency of 0 or more".into()),
+    Err(_) => Err("Please give a number".into()),
+  }
+}
+
+/// Validates if a ratio is a number that is not negative
+pub fn validate_ratio(ratio: String) -> std::result::Result<(), String> {
+  match ratio.parse::<f64>() {
+    Ok(parsed) if parsed.is_finite() && parsed >= 0.0 => Ok(()),
+    Ok(_) => Err("Please give a ratio of 0 or more".into()),
+    Err(_) => Err("Please give a number".into()),
+  }
+}
+
+/// Validates if a sampling rate is more than 0 and at most 1
+pub fn validate_rate(rate: String) -> std::result::Result<(), String> {
+  match rate.parse::<f64>() {
+    Ok(parsed) if parsed > 0.0 && parsed <= 1.0 => Ok(()),
+    Ok(_) => Err("Please give a rate more than 0 and at most 1".into()),
+    Err(_) => Err("Please give a number".into()),
+  }
+}
+
+/// Validates a whole number that can be 0, used for seeds and intervals
+pub fn validate_number(number: String) -> std::result::Result<(), String> {
+  number.parse::<u64>()
+    .map(|_| ())
+    .map_err(|_| "Please give a whole number".into())
+}
+
+/// Parses a range of table sizes in the form of `from..to`
+fn parse_size_range(range: &str) -> std::result::Result<(usize, usize), String> {
+  let mut split = range.splitn(2, "..");
+  let from = split.next().and_then(|x| x.parse::<usize>().ok());
+  let to = split.next().and_then(|x| x.parse::<usize>().ok());
+
+  match (from, to) {
+    (Some(from), Some(to)) if from > 0 && from <= to => Ok((from, to)),
+    _ => Err(format!("Invalid table size range {}, expected from..to with 0 < from <= to", range)),
+  }
+}
+
+/// Parses a list of table sizes separated by commas. Each item is either a
+/// single size `64`, a range `10..500` with an optional step `10..500:10` or
+/// a logarithmic range with a number of sizes `log:10..10000:40`.
+/// Returns the sizes sorted without duplicates
+pub fn parse_table_sizes(spec: &str) -> std::result::Result<Vec<usize>, String> {
+  let mut sizes = Vec::new();
+
+  for item in spec.split(',').map(|x| x.trim()) {
+    if let Some(log_range) = item.strip_prefix("log:") {
+      let mut split = log_range.splitn(2, ':');
+      let (from, to) = parse_size_range(split.next().unwrap_or(""))?;
+      let count = split.next()
+        .and_then(|x| x.parse::<usize>().ok())
+        .filter(|&x| x > 0)
+        .ok_or_else(|| format!("Invalid number of sizes in {}", item))?;
+
+      if count == 1 {
+        sizes.push(from);
+        continue;
+      }
+
+      // evenly spaced on a log scale, small sizes can round to duplicates
+      let ratio = (to as f64 / from as f64).powf(1.0 / (count - 1) as f64);
+      for i in 0..count {
+        let size = (from as f64 * ratio.powi(i as i32)).round() as usize;
+        sizes.push(size.clamp(from, to));
+      }
+    } else if item.contains("..") {
+      let mut split = item.splitn(2, ':');
+      let (from, to) = parse_size_range(split.next().unwrap_or(""))?;
+      let step = match split.next() {
+        Some(step) => step.parse::<usize>()
+          .ok()
+          .filter(|&x| x > 0)
+          .ok_or_else(|| format!("Invalid step in {}", item))?,
+        None => 1,
+      };
+
+      sizes.extend((from..=to).step_by(step));
+    } else {
+      match item.parse::<usize>() {
+        Ok(size) if size > 0 => sizes.push(size),
+        _ => return Err(format!("Invalid table size {}", item)),
+      }
+    }
+  }
+
+  sizes.sort_unstable();
+  sizes.dedup();
+
+  Ok(sizes)
+}
+
+/// Validates a list of table sizes
+pub fn validate_table_sizes(spec: String) -> std::result::Result<(), String> {
+  parse_table_sizes(&spec).map(|_| ())
+}
+
+/// Saves a vec of simulation results to a csv file
+pub fn save_result(output: &str, algorithm: &str, mut results: Vec<SimulationResult>)
   -> Result<()> {
   // sort vec, likely out of order due to multithreading
-  hit_rates.sort_by(|a, b| a.0.cmp(&b.0));
+  results.sort_by_key(|a| a.table_size);
 
   // format output with algorithm name
   let output = format!("{}.{}.csv", output.replace(".csv", ""), algorithm);
 
   // create new writer
   let mut wtr = Writer::from_path(&output)?;
-  // write header
-  wtr.write_record(&["table_size", algorithm])?;
-  // write each hit rate
-  for record in hit_rates {
-    wtr.write_record(&[record.0.to_string(), record.1.to_string()])?;
+  // repeated runs add the standard deviation and number of runs, the
+  // hit rate is their mean
+  let repeated = results.iter().any(|x| x.runs > 1);
+
+  // write header, hit rate column is named after the algorithm and the
+  // other columns are prefixed with it
+  let mut columns = vec!["secs", "eat_ns", "total_ns", "demand_faults", "prefetch_used",
+    "prefetch_wasted"];
+  if repeated {
+    columns.extend(&["stddev", "runs"]);
+  }
+  let mut header = vec!["table_size".to_string(), algorithm.to_string()];
+  header.extend(columns.iter().map(|x| format!("{}_{}", algorithm, x)));
+  wtr.write_record(&header)?;
+  // write each result
+  for record in results {
+    let mut row = vec![
+      record.table_size.to_string(),
+      record.hit_rate.to_string(),
+      record.elapsed_secs.to_string(),
+      record.effective_access_ns.to_string(),
+      record.total_ns.to_string(),
+      record.demand_faults.to_string(),
+      record.prefetch_used.to_string(),
+      record.prefetch_wasted.to_string(),
+    ];
+    if repeated {
+      row.push(record.hit_rate_stddev.to_string());
+      row.push(record.runs.to_string());
+    }
+    wtr.write_record(&row)?;
   }
   wtr.flush()?;
   
   info!("Saved hit rate data to {}", &output);
   Ok(())
 }
+
+/// Saves the page report of a single simulation as csv, the algorithm name
+/// and table size are inserted into the file name:
+/// pages.csv -> pages.lru.10.csv
+pub fn save_page_report<P: PageId>(output: &str, algorithm: &str, table_size: usize,
+  pages: &[PageSummary<P>]) -> Result<()> {
+  let output = format!("{}.{}.{}.csv", output.replace(".csv", ""), algorithm, table_size);
+
+  let mut wtr = Writer::from_path(&output)?;
+  for page in pages {
+    wtr.serialize(page)?;
+  }
+  wtr.flush()?;
+
+  info!("Saved page report to {}", &output);
+  Ok(())
+}
+
+/// Writes a table of every simulation result
+pub fn write_summary<W: Write>(out: &mut W, results: &[(String, Vec<SimulationResult>)])
+  -> Result<()> {
+  // prefetch columns are only useful with a prefetcher
+  let prefetched = results.iter()
+    .flat_map(|x| x.1.iter())
+    .any(|x| x.prefetch_used + x.prefetch_wasted > 0);
+  // standard deviation is only useful with repeated runs
+  let repeated = results.iter()
+    .flat_map(|x| x.1.iter())
+    .any(|x| x.runs > 1);
+
+  write!(out, "{:<16}{:<12}{:<12}", "algorithm", "table size", "hit rate")?;
+  if repeated {
+    write!(out, "{:<12}", "stddev")?;
+  }
+  write!(out, "{:<16}{:<16}", "eat ns", "total ms")?;
+  if prefetched {
+    write!(out, "{:<16}{:<16}{:<18}", "demand faults", "prefetch used", "prefetch wasted")?;
+  }
+  writeln!(out, "secs")?;
+
+  for (algorithm, algorithm_results) in results {
+    let mut algorithm_results: Vec<&SimulationResult> = algorithm_results.iter().collect();
+    algorithm_results.sort_by_key(|x| x.table_size);
+
+    for x in algorithm_results {
+      write!(out, "{:<16}{:<12}{:<12.5}", algorithm, x.table_size, x.hit_rate)?;
+      if repeated {
+        write!(out, "{:<12.5}", x.hit_rate_stddev)?;
+      }
+      write!(out, "{:<16.3}{:<16.3}", x.effective_access_ns, x.total_ns / 1e6)?;
+      if prefetched {
+        write!(out, "{:<16}{:<16}{:<18}", x.demand_faults, x.prefetch_used, x.prefetch_wasted)?;
+      }
+      writeln!(out, "{:.3}", x.elapsed_secs)?;
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,306 @@
+use crossterm::{
+  cursor,
+  event::{self, Event, KeyCode, KeyEventKind},
+  execute,
+  queue,
+  style::{Print, Stylize},
+  terminal::{self, ClearType},
+};
+use error::Result;
+use model::algorithms::{Outcome, TableState};
+use model::simulation::Simulation;
+use std::io::{self, Write};
+use std::sync::Arc;
+use util;
+
+/// Number of requests between saved simulation states, stepping backwards
+/// replays at most this many requests from the previous saved state
+const CHECKPOINT_INTERVAL: usize = 256;
+
+/// Number of requests shown before and after the current one
+const TRACE_CONTEXT: usize = 8;
+
+/// Options for an interactive visualization
+pub struct VisualizeOptions<'a> {
+  pub input: &'a str,
+  pub table_size: usize,
+  pub algorithm: &'a str,
+}
+
+/// Keeps track of a simulation at any position in the trace, allowing
+/// moving backwards by restoring a saved state and replaying requests
+struct Stepper {
+  /// Valid page requests
+  requests: Vec<u64>,
+  /// Simulation state after `position` requests
+  sim: Simulation,
+  /// Number of requests ran on `sim`
+  position: usize,
+  /// Saved states, `checkpoints[i]` is the state after
+  /// `i * CHECKPOINT_INTERVAL` requests
+  checkpoints: Vec<Simulation>,
+}
+
+impl Stepper {
+  fn new(requests: Vec<u64>, table_size: usize, algorithm: &str) -> Result<Self> {
+    // optimal looks ahead in the requests
+    let sim = Simulation::new(table_size, algorithm, Some(Arc::new(requests.clone())))?;
+
+    Ok(Stepper {
+      requests,
+      checkpoints: vec![sim.clone()],
+      sim,
+      position: 0,
+    })
+  }
+
+  /// Runs the next request
+  fn advance(&mut self) -> Result<Outcome> {
+    let outcome = self.sim.request(self.requests[self.position])?;
+    self.position += 1;
+
+    if self.position.is_multiple_of(CHECKPOINT_INTERVAL)
+      && self.checkpoints.len() == self.position / CHECKPOINT_INTERVAL {
+      self.checkpoints.push(self.sim.clone());
+    }
+
+    Ok(outcome)
+  }
+
+  /// Moves the simulation to the state after `target` requests
+  fn seek(&mut self, target: usize) -> Result<()> {
+    if target < self.position {
+      let index = (target / CHECKPOINT_INTERVAL).min(self.checkpoints.len() - 1);
+      self.sim = self.checkpoints[index].clone();
+      self.position = index * CHECKPOINT_INTERVAL;
+    }
+
+    while self.position < target {
+      self.advance()?;
+    }
+
+    Ok(())
+  }
+}
+
+/// What happened on a single step of the simulation
+struct StepView {
+  /// Number of requests ran, the request shown is `step - 1`
+  step: usize,
+  /// Table before the request and the result of the request,
+  /// None before the first request
+  before: Option<(TableState, Outcome)>,
+  /// Table after the request
+  after: TableState,
+  hits: u64,
+  faults: u64,
+}
+
+/// Runs the simulation to `step` and records the table before and after
+fn view_step(stepper: &mut Stepper, step: usize) -> Result<StepView> {
+  let before = if step > 0 {
+    stepper.seek(step - 1)?;
+    let before = stepper.sim.table_state();
+    let outcome = stepper.advance()?;
+    Some((before, outcome))
+  } else {
+    stepper.seek(0)?;
+    None
+  };
+
+  Ok(StepView {
+    step,
+    before,
+    after: stepper.sim.table_state(),
+    hits: stepper.sim.num_requests() - stepper.sim.num_misses(),
+    faults: stepper.sim.num_misses(),
+  })
+}
+
+/// Describes why a page was picked to be replaced
+fn eviction_reason(algorithm: &str, before: &TableState, after: &TableState,
+  evicted: u64, frame: usize, future: &[u64]) -> String {
+  match algorithm {
+    "fifo" => "oldest page in memory, at the clock hand".to_string(),
+    "lru" => format!("least recently used page, last used at time {}",
+      before.frames[frame].time.unwrap_or(0)),
+    "second_chance" | "sc" => {
+      // pages passed over by the clock hand lose their reference bit
+      let cleared: Vec<String> = before.frames
+        .iter()
+        .zip(after.frames.iter())
+        .filter(|&(b, a)| b.referenced == Some(true) && a.referenced == Some(false))
+        .filter_map(|(b, _)| b.page.map(|x| x.to_string()))
+        .collect();
+
+      if cleared.is_empty() {
+        "first unreferenced page at the clock hand".to_string()
+      } else {
+        format!("first unreferenced page from the clock hand, \
+          cleared reference bits of {}", cleared.join(", "))
+      }
+    },
+    "random" => "random page".to_string(),
+    "nru" => "random page from the lowest class of referenced and modified bits".to_string(),
+    "optimal" => match future.iter().position(|&x| x == evicted) {
+      Some(distance) => format!("page used furthest in the future, next request in {}",
+        distance + 1),
+      None => "page is not requested again".to_string(),
+    },
+    _ => String::new(),
+  }
+}
+
+/// Builds the lines of the screen for a step
+fn render(view: &StepView, requests: &[u64], options: &VisualizeOptions) -> Vec<String> {
+  let mut lines = Vec::new();
+
+  lines.push(format!("{} - table size {}, step {} / {}",
+    options.algorithm.to_uppercase(), options.table_size, view.step, requests.len())
+    .bold().to_string());
+  lines.push(String::new());
+
+  // index of the frame changed by this request
+  let mut changed = None;
+
+  match view.before {
+    Some((ref before, outcome)) => {
+      let request = requests[view.step - 1];
+
+      match outcome {
+        Outcome::Hit => lines.push(format!("Request {}: {}", request, "hit".green())),
+        Outcome::Fault { evicted: None, frame } => {
+          lines.push(format!("Request {}: {}", request, "fault".red()));
+          lines.push(format!("Loaded into free frame {}", frame));
+          changed = Some(frame);
+        },
+        Outcome::Fault { evicted: Some(evicted), frame } => {
+          lines.push(format!("Request {}: {}", request, "fault".red()));
+          lines.push(format!("Evicted page {} from frame {}", evicted, frame));
+          lines.push(format!("Reason: {}", eviction_reason(options.algorithm, before,
+            &view.after, evicted, frame, &requests[view.step..])));
+          changed = Some(frame);
+        },
+      }
+    },
+    None => lines.push("No requests made yet".to_string()),
+  }
+  lines.push(String::new());
+
+  // page table
+  let has_referenced = view.after.frames.iter().any(|x| x.referenced.is_some());
+  let has_time = view.after.frames.iter().any(|x| x.time.is_some());
+  let mut header = format!("  {:<8}{:<12}", "frame", "page");
+  if has_referenced {
+    header.push_str(&format!("{:<6}", "ref"));
+  }
+  if has_time {
+    header.push_str("time");
+  }
+  lines.push(header);
+
+  for (i, frame) in view.after.frames.iter().enumerate() {
+    let hand = if view.after.hand == Some(i) { ">" } else { " " };
+    let page = frame.page.map(|x| x.to_string()).unwrap_or_else(|| "-".to_string());
+    let mut row = format!("{} {:<8}{:<12}", hand, i, page);
+    if let Some(referenced) = frame.referenced {
+      row.push_str(&format!("{:<6}", referenced as u8));
+    }
+    if let Some(time) = frame.time {
+      row.push_str(&time.to_string());
+    }
+
+    if changed == Some(i) {
+      row = row.reverse().to_string();
+    }
+    lines.push(row);
+  }
+  lines.push(String::new());
+
+  // surrounding requests, current one is in brackets
+  let start = view.step.saturating_sub(TRACE_CONTEXT + 1);
+  let end = (view.step + TRACE_CONTEXT).min(requests.len());
+  let trace: Vec<String> = (start..end)
+    .map(|i| if i + 1 == view.step {
+      format!("[{}]", requests[i])
+    } else {
+      requests[i].to_string()
+    })
+    .collect();
+  lines.push(format!("Trace: {}", trace.join(" ")));
+
+  let hit_rate = if view.step > 0 { view.hits as f64 / view.step as f64 } else { 0.0 };
+  lines.push(format!("Hits: {}  Faults: {}  Hit rate: {:.5}", view.hits, view.faults, hit_rate));
+  lines.push(String::new());
+  lines.push("left/h back  right/l/space forward  pgup/pgdn 10 steps  \
+    home/end start/end  q quit".dim().to_string());
+
+  lines
+}
+
+/// Draws the screen, lines are written individually since raw mode does not
+/// return the cursor to the start of the line on newlines
+fn draw<W: Write>(out: &mut W, lines: &[String]) -> Result<()> {
+  queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+  for line in lines {
+    queue!(out, Print(line), cursor::MoveToNextLine(1))?;
+  }
+  out.flush()?;
+
+  Ok(())
+}
+
+/// Handles key input until the user quits
+fn event_loop<W: Write>(out: &mut W, stepper: &mut Stepper, requests: &[u64],
+  options: &VisualizeOptions) -> Result<()> {
+  let mut step = 0;
+  let last = requests.len();
+
+  loop {
+    let view = view_step(stepper, step)?;
+    draw(out, &render(&view, requests, options))?;
+
+    // redraw on resize or when the step changes, ignore key releases
+    loop {
+      let next = match event::read()? {
+        Event::Key(key) if key.kind != KeyEventKind::Release => match key.code {
+          KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+          KeyCode::Right | KeyCode::Char('l') | KeyCode::Char(' ') => (step + 1).min(last),
+          KeyCode::Left | KeyCode::Char('h') => step.saturating_sub(1),
+          KeyCode::PageDown => (step + 10).min(last),
+          KeyCode::PageUp => step.saturating_sub(10),
+          KeyCode::Home => 0,
+          KeyCode::End => last,
+          _ => continue,
+        },
+        Event::Resize(_, _) => break,
+        _ => continue,
+      };
+
+      if next != step {
+        step = next;
+        break;
+      }
+    }
+  }
+}
+
+/// Steps through a trace interactively, showing the page table contents
+/// after each request
+pub fn visualize(options: VisualizeOptions) -> Result<()> {
+  // only valid requests are kept so every step is a request
+  let requests = util::read_requests(Some(options.input))?;
+  let mut stepper = Stepper::new(requests.clone(), options.table_size, options.algorithm)?;
+
+  let mut out = io::stdout();
+  terminal::enable_raw_mode()?;
+  execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+  let res = event_loop(&mut out, &mut stepper, &requests, &options);
+
+  // always restore the terminal, even if drawing failed
+  execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+  terminal::disable_raw_mode()?;
+
+  res
+}